@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AnimationEvent>();
+    }
+}
+
+// The states of an entity that each have their own clip
+pub trait AnimationState: Copy + Eq + Hash + Debug + Send + Sync + 'static {
+    const ALL: &'static [Self];
+}
+
+// Raised when a clip reaches a frame that has an event, gameplay reacts to it by name
+#[derive(Event)]
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub name: String,
+}
+
+// Atlas indices of the frames of a clip: a run of consecutive ones, or any list
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Frames {
+    Range { first: usize, count: usize },
+    List(Vec<usize>),
+}
+
+impl Frames {
+    pub fn len(&self) -> usize {
+        match self {
+            Frames::Range { count, .. } => *count,
+            Frames::List(indices) => indices.len(),
+        }
+    }
+
+    pub fn index(&self, frame: usize) -> usize {
+        match self {
+            Frames::Range { first, .. } => first + frame,
+            Frames::List(indices) => indices[frame],
+        }
+    }
+
+    fn last_index(&self) -> usize {
+        (0..self.len()).map(|frame| self.index(frame)).max().unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+pub enum PlayMode {
+    #[default]
+    Loop,
+    // Played through once, then it moves on to its next state or holds its last frame
+    Once,
+    // Loops for that many seconds, then moves on to its next state
+    LoopFor(f32),
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrameEvent {
+    pub frame: usize,
+    pub name: String,
+}
+
+// How the frames of a sheet are cut, for a sheet that is not a single row
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SheetLayout {
+    pub columns: usize,
+    pub rows: usize,
+    #[serde(default)]
+    pub padding: (f32, f32),
+    #[serde(default)]
+    pub offset: (f32, f32),
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields, bound(deserialize = "S: Deserialize<'de>"))]
+pub struct AnimationClip<S> {
+    pub sheet: String,
+    pub frames: Frames,
+    pub frame_time: f32,
+    #[serde(default)]
+    pub mode: PlayMode,
+    // State the entity switches to once the clip is over
+    #[serde(default)]
+    pub next: Option<S>,
+    #[serde(default)]
+    pub events: Vec<FrameEvent>,
+}
+
+// Every clip of a kind of entity, one per state
+#[derive(Resource, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationSet<S: AnimationState> {
+    pub frame_size: (f32, f32),
+    #[serde(default)]
+    pub sheets: HashMap<String, SheetLayout>,
+    pub clips: HashMap<S, AnimationClip<S>>,
+}
+
+impl<S: AnimationState + DeserializeOwned> AnimationSet<S> {
+    pub fn load(path: &Path) -> Result<AnimationSet<S>, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read animations {}: {}", path.display(), err))?;
+        let animations: AnimationSet<S> = ron::from_str(&content)
+            .map_err(|err| format!("{}:{}", path.display(), err))?;
+        animations.validate().map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(animations)
+    }
+}
+
+impl<S: AnimationState> AnimationSet<S> {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.frame_size.0 <= 0. || self.frame_size.1 <= 0. {
+            return Err("frame_size must be positive".to_string());
+        }
+        for state in S::ALL {
+            let Some(clip) = self.clips.get(state) else {
+                return Err(format!("no clip for {:?}", state));
+            };
+            if clip.frames.len() == 0 || clip.frame_time <= 0. {
+                return Err(format!("clip {:?} needs at least one frame and a positive frame_time", state));
+            }
+            if let PlayMode::LoopFor(duration) = clip.mode {
+                if duration <= 0. {
+                    return Err(format!("clip {:?} must loop for a positive duration", state));
+                }
+            }
+            if let Some(event) = clip.events.iter().find(|event| event.frame >= clip.frames.len()) {
+                return Err(format!("event {} of clip {:?} is on frame {}, past its {} frames", event.name, state, event.frame, clip.frames.len()));
+            }
+            let layout = self.layout(&clip.sheet);
+            if clip.frames.last_index() >= layout.columns * layout.rows {
+                return Err(format!("clip {:?} goes past the {} frames of {}", state, layout.columns * layout.rows, clip.sheet));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clip(&self, state: S) -> &AnimationClip<S> {
+        &self.clips[&state]
+    }
+
+    pub fn has_event(&self, state: S, name: &str) -> bool {
+        self.clips.get(&state).is_some_and(|clip| clip.events.iter().any(|event| event.name == name))
+    }
+
+    // A sheet left out of `sheets` is a single row, as long as the furthest frame any clip shows of it
+    pub fn layout(&self, sheet: &str) -> SheetLayout {
+        self.sheets.get(sheet).copied().unwrap_or_else(|| {
+            let columns = self.clips.values()
+                .filter(|clip| clip.sheet == sheet)
+                .map(|clip| clip.frames.last_index() + 1)
+                .max()
+                .unwrap_or(1);
+            SheetLayout { columns, rows: 1, padding: (0., 0.), offset: (0., 0.) }
+        })
+    }
+}
+
+// Plays the clip of the state its entity is in, from the start each time the state changes
+#[derive(Component)]
+pub struct Animator<S: AnimationState> {
+    state: S,
+    frame: usize,
+    frame_time: f32,
+    elapsed: f32,
+    // The events of the first frame are still to be raised
+    started: bool,
+}
+
+impl<S: AnimationState> Animator<S> {
+    pub fn new(state: S) -> Self {
+        Animator { state, frame: 0, frame_time: 0., elapsed: 0., started: false }
+    }
+
+    pub fn play(&mut self, state: S) {
+        if state != self.state {
+            *self = Animator::new(state);
+        }
+    }
+
+    pub fn atlas_index(&self, animations: &AnimationSet<S>) -> usize {
+        animations.clip(self.state).frames.index(self.frame)
+    }
+
+    pub fn sheet<'a>(&self, animations: &'a AnimationSet<S>) -> &'a str {
+        &animations.clip(self.state).sheet
+    }
+
+    // One tick further in the clip, raising the events of the frames it reaches.
+    // The state to switch to when the clip is over
+    pub fn advance(&mut self, animations: &AnimationSet<S>, delta: f32, mut on_event: impl FnMut(&str)) -> Option<S> {
+        let clip = animations.clip(self.state);
+        let raise = |frame: usize, on_event: &mut dyn FnMut(&str)| {
+            clip.events.iter().filter(|event| event.frame == frame).for_each(|event| on_event(&event.name));
+        };
+        if !self.started {
+            self.started = true;
+            raise(self.frame, &mut on_event);
+        }
+
+        self.frame_time += delta;
+        self.elapsed += delta;
+        if self.frame_time >= clip.frame_time {
+            self.frame_time = 0.;
+            if self.frame + 1 < clip.frames.len() {
+                self.frame += 1;
+            } else if let PlayMode::Once = clip.mode {
+                return clip.next;
+            } else {
+                self.frame = 0;
+            }
+            raise(self.frame, &mut on_event);
+        }
+        match clip.mode {
+            PlayMode::LoopFor(duration) if self.elapsed >= duration => clip.next,
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use serde::Deserialize;
+use crate::animation::*;
+use crate::animation::AnimationClip;
+use crate::config::GameConfig;
+use crate::constants::*;
+use crate::ennemies::EnnemyState;
+
+// Shooting from afar instead of striking in melee
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RangedAttack {
+    // Distance kept from the player while shooting
+    pub preferred_distance: f32,
+    // How far a projectile flies
+    pub range: f32,
+    pub reload_time: f32,
+    pub projectile_speed: f32,
+}
+
+// A kind of ennemy, read from a RON file of assets/ennemies named after it
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnnemyArchetype {
+    #[serde(skip)]
+    pub name: String,
+    // Chance to be picked at spawn, relative to the other archetypes
+    pub spawn_weight: u32,
+
+    pub health: i32,
+    pub attack: i32,
+    pub defence_ratio: f32,
+    pub normal_speed: f32,
+    pub sprint_speed: f32,
+    pub aggro_distance: f32,
+    pub hitbox: (f32, f32),
+    // Speed the player is pushed away at by its blows, or its projectiles
+    #[serde(default = "default_knockback")]
+    pub knockback: f32,
+    // Seconds it cannot be hurt again after a hit
+    #[serde(default = "default_invulnerability")]
+    pub invulnerability: f32,
+
+    pub sprite_scale: f32,
+    #[serde(default = "no_tint")]
+    pub tint: (f32, f32, f32),
+    // The "hit" event of the attack clip is when the blow lands, or the projectile is shot
+    pub animations: AnimationSet<EnnemyState>,
+    // None for an ennemy fighting in melee
+    #[serde(default)]
+    pub ranged: Option<RangedAttack>,
+}
+
+fn no_tint() -> (f32, f32, f32) {
+    (1., 1., 1.)
+}
+
+fn default_knockback() -> f32 {
+    ENNEMY_KNOCKBACK
+}
+
+fn default_invulnerability() -> f32 {
+    ENNEMY_INVULNERABILITY
+}
+
+impl Default for EnnemyArchetype {
+    // The skeleton, for when there is no assets/ennemies folder
+    fn default() -> Self {
+        let clip = |sheet: &str, frames: Frames, frame_time: f32, next: Option<EnnemyState>| AnimationClip {
+            sheet: format!("Skeleton/{}.png", sheet),
+            frames,
+            frame_time,
+            mode: if next.is_some() { PlayMode::Once } else { PlayMode::Loop },
+            next,
+            events: Vec::new(),
+        };
+        let row = |count: usize| Frames::Range { first: 0, count };
+        let mut attack = clip("Attack", row(8), ENNEMY_ATTACK_SPEED / 8., Some(EnnemyState::Chasing));
+        attack.events.push(FrameEvent { frame: 6, name: "hit".to_string() });
+        EnnemyArchetype {
+            name: "skeleton".to_string(),
+            spawn_weight: 1,
+            health: ENNEMY_HEALTH,
+            attack: ENNEMY_DAMAGE,
+            defence_ratio: ENNEMY_DEFENCE_RATIO,
+            normal_speed: ENNEMY_NORMAL_SPEED,
+            sprint_speed: ENNEMY_SPRINT_SPEED,
+            aggro_distance: ENNEMY_AGGRO_DISTANCE,
+            hitbox: (ENNEMY_HITBOX_WIDTH, ENNEMY_HITBOX_HEIGHT),
+            knockback: ENNEMY_KNOCKBACK,
+            invulnerability: ENNEMY_INVULNERABILITY,
+            sprite_scale: ENNEMY_SPRITE_SCALE,
+            tint: no_tint(),
+            animations: AnimationSet {
+                frame_size: (150., 150.),
+                sheets: HashMap::new(),
+                clips: HashMap::from([
+                    (EnnemyState::Loading, clip("Idle", Frames::List(vec![0]), 0.3, None)),
+                    (EnnemyState::Idle, clip("Idle", row(4), 0.3, None)),
+                    (EnnemyState::Roaming, clip("Walk", row(4), 0.3, None)),
+                    (EnnemyState::Searching, clip("Walk", row(4), 0.3, None)),
+                    (EnnemyState::Chasing, clip("Walk", row(4), 0.1, None)),
+                    (EnnemyState::Damaged, clip("Take Hit", row(4), 0.2, Some(EnnemyState::Chasing))),
+                    (EnnemyState::Attacking, attack),
+                    (EnnemyState::Blocking, clip("Shield", row(4), 0.1, Some(EnnemyState::Chasing))),
+                    (EnnemyState::Dying, clip("Death", row(4), 0.2, Some(EnnemyState::Dead))),
+                    (EnnemyState::Dead, clip("Death", Frames::List(vec![3]), 0.2, None)),
+                ]),
+            },
+            ranged: None,
+        }
+    }
+}
+
+impl EnnemyArchetype {
+    fn validate(&self, game_config: &GameConfig) -> Result<(), String> {
+        if self.health <= 0 {
+            return Err(format!("health must be positive, got {}", self.health));
+        }
+        if self.attack < 0 {
+            return Err(format!("attack cannot be negative, got {}", self.attack));
+        }
+        if !(0. ..=1.).contains(&self.defence_ratio) {
+            return Err(format!("defence_ratio must be between 0 and 1, got {}", self.defence_ratio));
+        }
+        if self.knockback < 0. || self.invulnerability < 0. {
+            return Err(format!("knockback and invulnerability cannot be negative, got {} and {}", self.knockback, self.invulnerability));
+        }
+        if self.normal_speed <= 0. || self.sprint_speed <= 0. {
+            return Err("every speed must be positive".to_string());
+        }
+        // Ennemies spawn outside of their aggro distance around the player, there must be room left for them
+        let max_aggro_distance = game_config.map_size / 2. - SANCTUARY_WIDTH / 2.;
+        if self.aggro_distance <= 0. || self.aggro_distance >= max_aggro_distance {
+            return Err(format!("aggro_distance must be between 0 and {} for this map_size, got {}",
+                max_aggro_distance, self.aggro_distance));
+        }
+        if self.hitbox.0 <= 0. || self.hitbox.1 <= 0. || self.sprite_scale <= 0. {
+            return Err("hitbox and sprite_scale must be positive".to_string());
+        }
+        self.animations.validate()?;
+        // Without it the ennemy would never land a blow
+        if !self.animations.has_event(EnnemyState::Attacking, "hit") {
+            return Err("the Attacking clip needs a \"hit\" event".to_string());
+        }
+        if let Some(ranged) = &self.ranged {
+            if ranged.preferred_distance <= 0. || ranged.reload_time <= 0. || ranged.projectile_speed <= 0. {
+                return Err("preferred_distance, reload_time and projectile_speed of ranged must be positive".to_string());
+            }
+            if ranged.range <= ranged.preferred_distance {
+                return Err(format!("ranged range must be above its preferred_distance, got {} for {}",
+                    ranged.range, ranged.preferred_distance));
+            }
+        }
+        Ok(())
+    }
+
+    // Difficulty of the game config on top of the values of the file
+    fn scaled(mut self, game_config: &GameConfig) -> Self {
+        self.attack = (self.attack as f32 * game_config.ennemy_damage_multiplier).round() as i32;
+        self.aggro_distance *= game_config.ennemy_aggro_multiplier;
+        self
+    }
+}
+
+// Every kind of ennemy and the weighted table spawns pick them from.
+// Ennemies share their archetype with the registry instead of keeping a copy of it
+#[derive(Resource, Clone)]
+pub struct EnnemyArchetypes {
+    archetypes: Vec<Arc<EnnemyArchetype>>,
+    weights: WeightedIndex<u32>,
+    // As read from the files, before the config scales them
+    unscaled: Vec<EnnemyArchetype>,
+}
+
+impl EnnemyArchetypes {
+    // Every .ron file of the folder, in name order so a seed always picks the same ones
+    pub fn load(dir: &Path, game_config: &GameConfig) -> Result<EnnemyArchetypes, String> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(|err| format!("cannot read ennemy archetypes {}: {}", dir.display(), err))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+            .collect();
+        paths.sort();
+
+        let mut archetypes = Vec::new();
+        for path in paths {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|err| format!("cannot read ennemy archetype {}: {}", path.display(), err))?;
+            let mut archetype: EnnemyArchetype = ron::from_str(&content)
+                .map_err(|err| format!("{}:{}", path.display(), err))?;
+            archetype.name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            archetypes.push(archetype);
+        }
+        if archetypes.is_empty() {
+            return Err(format!("no ennemy archetype in {}", dir.display()));
+        }
+        EnnemyArchetypes::new(archetypes, game_config)
+    }
+
+    pub fn new(unscaled: Vec<EnnemyArchetype>, game_config: &GameConfig) -> Result<EnnemyArchetypes, String> {
+        let archetypes: Vec<Arc<EnnemyArchetype>> = unscaled.iter().map(|archetype| Arc::new(archetype.clone().scaled(game_config))).collect();
+        for archetype in archetypes.iter() {
+            archetype.validate(game_config).map_err(|err| format!("ennemy archetype {}: {}", archetype.name, err))?;
+        }
+        let weights = WeightedIndex::new(archetypes.iter().map(|archetype| archetype.spawn_weight))
+            .map_err(|_| "at least one ennemy archetype needs a positive spawn_weight".to_string())?;
+        Ok(EnnemyArchetypes { archetypes, weights, unscaled })
+    }
+
+    // The same archetypes scaled for another config, the one of a continued game
+    pub fn for_config(&self, game_config: &GameConfig) -> Result<EnnemyArchetypes, String> {
+        EnnemyArchetypes::new(self.unscaled.clone(), game_config)
+    }
+
+    pub fn pick(&self, rng: &mut impl Rng) -> &Arc<EnnemyArchetype> {
+        &self.archetypes[self.weights.sample(rng)]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<EnnemyArchetype>> {
+        self.archetypes.iter().find(|archetype| archetype.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &EnnemyArchetype> {
+        self.archetypes.iter().map(Arc::as_ref)
+    }
+
+    // Paths are planned for the biggest ennemy, the others fit wherever it does
+    pub fn largest_hitbox(&self) -> Vec2 {
+        self.archetypes.iter()
+            .map(|archetype| Vec2::new(archetype.hitbox.0, archetype.hitbox.1))
+            .fold(Vec2::ZERO, Vec2::max)
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use crate::animation::*;
+use crate::archetypes::EnnemyArchetypes;
+use crate::constants::*;
+use crate::headless::is_rendering;
+use crate::player::PlayerState;
+
+pub struct AtlasPlugin;
+
+impl Plugin for AtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AtlasRegistry>()
+            .add_systems(Startup, build_atlas_registry.run_if(is_rendering));
+    }
+}
+
+// Sheets of the decor, cut the same way for every map
+const DECOR_SHEETS: [(&str, (f32, f32), SheetLayout); 4] = [
+    (TREES_SHEET, (TREE_WIDTH, TREE_HEIGHT), SheetLayout { columns: 3, rows: 1, padding: (0., 0.), offset: (3., 0.) }),
+    (BUSHES_SHEET, (BUSH_WIDTH, BUSH_HEIGHT), SheetLayout { columns: 3, rows: 1, padding: (0., 0.), offset: (0., 0.) }),
+    (GRAVES_SHEET, (GRAVE_WIDTH, GRAVE_HEIGHT), SheetLayout { columns: 3, rows: 1, padding: (0., 0.), offset: (0., 0.) }),
+    (SANCTUARY_SHEET, (SANCTUARY_WIDTH, SANCTUARY_HEIGHT), SheetLayout { columns: 2, rows: 1, padding: (1., 0.), offset: (0., 0.) }),
+];
+
+// Images drawn whole, by the map, the HUD and the menus
+const IMAGES: [&str; 5] = [BACKGROUND_IMAGE, TOWER_IMAGE, SANCTUARY_MARKER_IMAGE, BUTTON_IMAGE, PRESSED_BUTTON_IMAGE];
+
+// Every sprite sheet of the game cut once at startup, by the path of its image, so spawning and
+// animating only hand out handles. It also holds every image the game needs, sheets included,
+// for the loading screen to wait on. Empty without a renderer
+#[derive(Resource, Default)]
+pub struct AtlasRegistry {
+    atlases: HashMap<String, Handle<TextureAtlas>>,
+    images: HashMap<String, Handle<Image>>,
+}
+
+impl AtlasRegistry {
+    pub fn atlas(&self, sheet: &str) -> Option<Handle<TextureAtlas>> {
+        self.atlases.get(sheet).cloned()
+    }
+
+    pub fn image(&self, path: &str) -> Option<Handle<Image>> {
+        self.images.get(path).cloned()
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = (&str, &Handle<Image>)> {
+        self.images.iter().map(|(path, handle)| (path.as_str(), handle))
+    }
+
+    fn add_image(&mut self, asset_server: &AssetServer, path: &str) -> Handle<Image> {
+        self.images.entry(path.to_string()).or_insert_with(|| asset_server.load(path.to_string())).clone()
+    }
+
+    // A sheet shared by several kinds of entities is cut once, check_sheet_cuts made sure they all cut it the same way
+    fn add_sheet(&mut self, texture_atlases: &mut Assets<TextureAtlas>, asset_server: &AssetServer, sheet: &str, (width, height): (f32, f32), layout: SheetLayout) {
+        if self.atlases.contains_key(sheet) {
+            return;
+        }
+        let texture = self.add_image(asset_server, sheet);
+        let texture_atlas = TextureAtlas::from_grid(texture, Vec2::new(width, height), layout.columns, layout.rows,
+            Some(Vec2::new(layout.padding.0, layout.padding.1)), Some(Vec2::new(layout.offset.0, layout.offset.1)));
+        self.atlases.insert(sheet.to_string(), texture_atlases.add(texture_atlas));
+    }
+}
+
+// Every sheet of the game with the frame size and layout it is cut with, a sheet once per clip using it
+fn sheet_cuts<'a>(player_animations: &'a AnimationSet<PlayerState>, ennemy_archetypes: &'a EnnemyArchetypes) -> Vec<(&'a str, (f32, f32), SheetLayout)> {
+    let mut cuts = animation_cuts(player_animations);
+    for archetype in ennemy_archetypes.iter() {
+        cuts.extend(animation_cuts(&archetype.animations));
+    }
+    cuts.extend(DECOR_SHEETS);
+    cuts
+}
+
+fn animation_cuts<S: AnimationState>(animations: &AnimationSet<S>) -> Vec<(&str, (f32, f32), SheetLayout)> {
+    animations.clips.values()
+        .map(|clip| (clip.sheet.as_str(), animations.frame_size, animations.layout(&clip.sheet)))
+        .collect()
+}
+
+// A sheet cut with other frames than the ones its clips expect would show garbled sprites
+pub fn check_sheet_cuts(player_animations: &AnimationSet<PlayerState>, ennemy_archetypes: &EnnemyArchetypes) -> Result<(), String> {
+    let mut cuts: HashMap<&str, ((f32, f32), SheetLayout)> = HashMap::new();
+    for (sheet, frame_size, layout) in sheet_cuts(player_animations, ennemy_archetypes) {
+        let (cut_size, cut_layout) = *cuts.entry(sheet).or_insert((frame_size, layout));
+        if cut_size != frame_size || cut_layout != layout {
+            return Err(format!("sheet {} is cut in two different ways, {:?} frames in {}x{} and {:?} frames in {}x{}",
+                sheet, cut_size, cut_layout.columns, cut_layout.rows, frame_size, layout.columns, layout.rows));
+        }
+    }
+    Ok(())
+}
+
+fn build_atlas_registry(
+    mut registry: ResMut<AtlasRegistry>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    player_animations: Res<AnimationSet<PlayerState>>,
+    ennemy_archetypes: Res<EnnemyArchetypes>,
+) {
+    for (sheet, frame_size, layout) in sheet_cuts(&player_animations, &ennemy_archetypes) {
+        registry.add_sheet(&mut texture_atlases, &asset_server, sheet, frame_size, layout);
+    }
+    for path in IMAGES {
+        registry.add_image(&asset_server, path);
+    }
+    println!("{} texture atlases ready, {} images to load", registry.atlases.len(), registry.images.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::archetypes::EnnemyArchetype;
+    use crate::config::GameConfig;
+
+    #[test]
+    fn shipped_sheets_are_cut_one_way() {
+        let game_config = GameConfig::default();
+        let player_animations = AnimationSet::load(Path::new(PLAYER_ANIMATIONS_PATH)).unwrap();
+        let ennemy_archetypes = EnnemyArchetypes::load(Path::new(ENNEMY_ARCHETYPES_PATH), &game_config).unwrap();
+        assert!(check_sheet_cuts(&player_animations, &ennemy_archetypes).is_ok());
+    }
+
+    #[test]
+    fn sheet_cut_with_other_frames_is_rejected() {
+        let game_config = GameConfig::default();
+        let player_animations = AnimationSet::default();
+        let skeleton = EnnemyArchetype::default();
+        let larger = EnnemyArchetype {
+            name: "larger".to_string(),
+            animations: AnimationSet { frame_size: (100., 100.), ..skeleton.animations.clone() },
+            ..skeleton.clone()
+        };
+        let ennemy_archetypes = EnnemyArchetypes::new(vec![skeleton, larger], &game_config).unwrap();
+        let err = check_sheet_cuts(&player_animations, &ennemy_archetypes).unwrap_err();
+        assert!(err.contains("Skeleton/"), "{}", err);
+    }
+}
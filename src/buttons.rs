@@ -1,4 +1,6 @@
 use bevy::prelude::*;
+use crate::atlases::AtlasRegistry;
+use crate::constants::*;
 
 pub struct ButtonPlugin;
 
@@ -23,7 +25,7 @@ pub fn create_button<T: Component>(
     button_component: T,
     asset_server: &Res<AssetServer>, 
 ) {
-    let texture: Handle<Image> = asset_server.load("UI/button.png");
+    let texture: Handle<Image> = asset_server.load(BUTTON_IMAGE);
     
     commands
         .spawn((ButtonBundle {
@@ -63,21 +65,26 @@ pub fn create_button<T: Component>(
 
 fn button_animation(
     mut button_query: Query<(&Interaction, &mut Style, &mut UiImage), With<ButtonCompo>>,
-    asset_server: Res<AssetServer>,
+    atlases: Res<AtlasRegistry>,
 ) {
-    let not_pressed: Handle<Image> = asset_server.load("UI/button.png");
-    let pressed: Handle<Image> = asset_server.load("UI/pressed_button.png");
+    let (Some(not_pressed), Some(pressed)) = (atlases.image(BUTTON_IMAGE), atlases.image(PRESSED_BUTTON_IMAGE)) else {
+        return;
+    };
     for (interaction, mut style, mut image) in button_query.iter_mut() {
 
         match *interaction {
             Interaction::Pressed => {
-                *image = UiImage::new(pressed.clone());
+                if image.texture != pressed {
+                    *image = UiImage::new(pressed.clone());
+                }
             }
             Interaction::Hovered => {
                 style.padding = UiRect::axes(Val::Px(10.0), Val::Px(20.0));
             }
             Interaction::None => {
-                *image = UiImage::new(not_pressed.clone());
+                if image.texture != not_pressed {
+                    *image = UiImage::new(not_pressed.clone());
+                }
                 style.padding = UiRect::all(Val::Px(20.0));
             }
         }
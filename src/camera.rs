@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowMode, WindowResized};
+use crate::config::GameConfig;
+use crate::constants::*;
+use crate::player::{self, Player};
+use crate::GameState;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ViewArea>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(OnExit(GameState::Loading), snap_camera)
+            .add_systems(Update, (update_view_area, toggle_fullscreen))
+            .add_systems(Update, (zoom_camera,
+                                                    add_trauma,
+                                                    track_player.after(player::update_player_pos),
+                                                ).chain().after(update_view_area).run_if(in_state(GameState::Playing)));
+    }
+}
+
+// Sent by gameplay to shake the screen, trauma adds up to 1 and fades out over time
+#[derive(Event)]
+pub struct CameraShake {
+    pub trauma: f32,
+}
+
+#[derive(Component)]
+pub struct CameraController {
+    pub smoothing: f32,
+    pub dead_zone: Vec2,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    pub zoom_speed: f32,
+    pub trauma_decay: f32,
+    pub max_shake_offset: f32,
+    pub max_shake_angle: f32,
+    trauma: f32,
+    focus: Vec2,
+    snap: bool,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController {
+            smoothing: CAMERA_SMOOTHING,
+            dead_zone: Vec2::new(CAMERA_DEAD_ZONE_WIDTH, CAMERA_DEAD_ZONE_HEIGHT),
+            min_scale: CAMERA_MIN_SCALE,
+            max_scale: CAMERA_MAX_SCALE,
+            zoom_speed: CAMERA_ZOOM_SPEED,
+            trauma_decay: CAMERA_TRAUMA_DECAY,
+            max_shake_offset: CAMERA_SHAKE_MAX_OFFSET,
+            max_shake_angle: CAMERA_SHAKE_MAX_ANGLE,
+            trauma: 0.,
+            focus: Vec2::ZERO,
+            snap: true,
+        }
+    }
+}
+
+impl CameraController {
+    // Moves the focus just enough to keep `target` inside the dead zone, then eases toward it
+    fn follow(&mut self, target: Vec2, delta: f32) {
+        if self.snap {
+            self.focus = target;
+            self.snap = false;
+            return;
+        }
+
+        let half_dead_zone = self.dead_zone / 2.;
+        let offset = target - self.focus;
+        let wanted = self.focus + offset - offset.clamp(-half_dead_zone, half_dead_zone);
+        self.focus += (wanted - self.focus) * (1. - (-self.smoothing * delta).exp());
+    }
+
+    // Offset and angle of the shake at `time`, squared trauma so small hits stay subtle
+    fn shake(&self, time: f32) -> (Vec2, f32) {
+        let shake = self.trauma * self.trauma;
+        let t = time * CAMERA_SHAKE_FREQUENCY;
+        let offset = Vec2::new(
+            (t * 1.0).sin() * 0.6 + (t * 2.3 + 1.7).sin() * 0.4,
+            (t * 1.3 + 4.2).sin() * 0.6 + (t * 2.9 + 0.5).sin() * 0.4,
+        );
+        let angle = (t * 0.9 + 2.8).sin() * 0.7 + (t * 2.1 + 3.3).sin() * 0.3;
+        (offset * self.max_shake_offset * shake, angle * self.max_shake_angle * shake)
+    }
+}
+
+// Half size of the world rectangle seen through the camera, kept in sync with the window size and the zoom
+#[derive(Resource, Default)]
+pub struct ViewArea {
+    pub half_width: f32,
+    pub half_height: f32,
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn((Camera2dBundle {
+        projection: OrthographicProjection {
+            scale : CAMERA_DEFAULT_SCALE,
+            far: Z_LAYER_GUI,
+            ..OrthographicProjection::default()
+        },
+        ..Default::default()
+    }, CameraController::default()));
+}
+
+// A new game jumps straight to the player instead of sliding over from the last one
+fn snap_camera(mut query: Query<&mut CameraController>) {
+    for mut controller in query.iter_mut() {
+        controller.snap = true;
+        controller.trauma = 0.;
+    }
+}
+
+fn update_view_area(
+    mut view_area: ResMut<ViewArea>,
+    mut resized: EventReader<WindowResized>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    projection_query: Query<Ref<OrthographicProjection>, With<Camera>>,
+) {
+    let window_resized = resized.iter().count() > 0;
+    let Ok(window) = windows.get_single() else { return };
+    let Ok(projection) = projection_query.get_single() else { return };
+
+    if window_resized || projection.is_changed() {
+        view_area.half_width = window.width() * projection.scale / 2.;
+        view_area.half_height = window.height() * projection.scale / 2.;
+    }
+}
+
+fn toggle_fullscreen(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    game_config: Res<GameConfig>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F11) {
+        let mut window = windows.single_mut();
+        window.mode = match window.mode {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => {
+                window.resolution.set(game_config.resolution.0, game_config.resolution.1);
+                WindowMode::Windowed
+            }
+        };
+    }
+}
+
+fn zoom_camera(
+    mut query: Query<(&mut OrthographicProjection, &CameraController)>,
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+) {
+    let (mut projection, controller) = query.single_mut();
+    let step = controller.zoom_speed * time.delta_seconds();
+    if keyboard_input.pressed(KeyCode::S) {
+        projection.scale = (projection.scale + step).min(controller.max_scale);
+    }
+    if keyboard_input.pressed(KeyCode::Z) {
+        projection.scale = (projection.scale - step).max(controller.min_scale);
+    }
+    if keyboard_input.just_pressed(KeyCode::R) {
+        projection.scale = CAMERA_DEFAULT_SCALE;
+    }
+    if keyboard_input.just_pressed(KeyCode::M) {
+        projection.scale = controller.max_scale;
+    }
+}
+
+fn add_trauma(
+    mut shakes: EventReader<CameraShake>,
+    mut query: Query<&mut CameraController>,
+    time: Res<Time>,
+) {
+    let mut controller = query.single_mut();
+    controller.trauma = (controller.trauma - controller.trauma_decay * time.delta_seconds()).max(0.);
+    for shake in shakes.iter() {
+        controller.trauma = (controller.trauma + shake.trauma).min(1.);
+    }
+}
+
+pub fn track_player(
+    player_query: Query<&Transform, With<Player>>,
+    mut camera: Query<(&mut Transform, &mut CameraController), Without<Player>>,
+    view_area: Res<ViewArea>,
+    game_config: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    let player_transform = player_query.single();
+    let (mut camera_transform, mut controller) = camera.single_mut();
+
+    controller.follow(player_transform.translation.truncate(), time.delta_seconds());
+
+    // The view area already accounts for the zoom, a map smaller than the view stays centered
+    let camera_max_x = (game_config.map_size / 2. - view_area.half_width).max(0.);
+    let camera_max_y = (game_config.map_size / 2. - view_area.half_height).max(0.);
+    controller.focus = controller.focus.clamp(Vec2::new(-camera_max_x, -camera_max_y), Vec2::new(camera_max_x, camera_max_y));
+
+    let (offset, angle) = controller.shake(time.elapsed_seconds());
+    camera_transform.translation.x = controller.focus.x + offset.x;
+    camera_transform.translation.y = controller.focus.y + offset.y;
+    camera_transform.rotation = Quat::from_rotation_z(angle);
+}
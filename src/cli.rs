@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueEnum};
+use crate::animation::AnimationSet;
+use crate::archetypes::{EnnemyArchetype, EnnemyArchetypes};
+use crate::config::GameConfig;
+use crate::constants::*;
+use crate::player::PlayerState;
+
+#[derive(Parser)]
+#[command(name = "zelda-rust", about = "Unlock every sanctuary of a graveyard full of skeletons")]
+pub struct Cli {
+    /// Seed of the map and of every gameplay random draw
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Play in a window
+    #[arg(long, conflicts_with = "fullscreen")]
+    pub windowed: bool,
+
+    /// Play in borderless fullscreen
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Window size, e.g. 1280x720
+    #[arg(long, value_name = "WxH", value_parser = parse_resolution)]
+    pub resolution: Option<(f32, f32)>,
+
+    /// Number of enemies on the map
+    #[arg(long, value_name = "N")]
+    pub enemies: Option<u32>,
+
+    /// Width and height of the square map
+    #[arg(long, value_name = "SIZE")]
+    pub map_size: Option<f32>,
+
+    /// Balance preset applied on top of the config file
+    #[arg(long, value_enum, default_value_t = Difficulty::Normal)]
+    pub difficulty: Difficulty,
+
+    /// Start playing right away instead of showing the main menu
+    #[arg(long)]
+    pub skip_menu: bool,
+
+    /// Balance file, defaults to config.ron when it exists
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Simulate the game without a window
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Ticks to simulate in headless mode
+    #[arg(long, requires = "headless", default_value_t = HEADLESS_DEFAULT_TICKS)]
+    pub ticks: u32,
+
+    /// Record the inputs of the run, defaults to last_run.replay when not headless
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Replay a recorded run, with the seed it was recorded with
+    #[arg(long, value_name = "FILE", conflicts_with = "seed")]
+    pub replay: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    // Scales the balance values read from the config file, the explicit options are applied after
+    fn apply(&self, game_config: &mut GameConfig) {
+        match self {
+            Difficulty::Easy => {
+                game_config.player_health = game_config.player_health * 3 / 2;
+                game_config.ennemy_damage_multiplier *= 0.6;
+                game_config.ennemy_aggro_multiplier *= 0.75;
+            }
+            Difficulty::Normal => {}
+            Difficulty::Hard => {
+                game_config.player_health = game_config.player_health * 3 / 4;
+                game_config.ennemy_damage_multiplier *= 1.6;
+                game_config.ennemy_aggro_multiplier *= 1.25;
+            }
+        }
+    }
+}
+
+impl Cli {
+    pub fn game_config(&self) -> Result<GameConfig, String> {
+        let mut game_config = match &self.config {
+            Some(path) => GameConfig::load(path)?,
+            None if Path::new(CONFIG_DEFAULT_PATH).exists() => GameConfig::load(Path::new(CONFIG_DEFAULT_PATH))?,
+            None => GameConfig::default(),
+        };
+
+        self.difficulty.apply(&mut game_config);
+        if let Some(seed) = self.seed {
+            game_config.seed = seed;
+        }
+        if let Some(enemies) = self.enemies {
+            game_config.ennemies_number = enemies;
+        }
+        if let Some(map_size) = self.map_size {
+            game_config.map_size = map_size;
+        }
+        if self.windowed {
+            game_config.fullscreen = false;
+        }
+        if self.fullscreen {
+            game_config.fullscreen = true;
+        }
+        if let Some(resolution) = self.resolution {
+            game_config.resolution = resolution;
+        }
+
+        game_config.validate()?;
+        Ok(game_config)
+    }
+
+    // The archetypes of assets/ennemies, or the plain skeleton when the folder is missing
+    pub fn ennemy_archetypes(&self, game_config: &GameConfig) -> Result<EnnemyArchetypes, String> {
+        let dir = Path::new(ENNEMY_ARCHETYPES_PATH);
+        let ennemy_archetypes = if dir.exists() {
+            EnnemyArchetypes::load(dir, game_config)?
+        } else {
+            EnnemyArchetypes::new(vec![EnnemyArchetype::default()], game_config)?
+        };
+        // Waves of the config can only ask for an archetype that exists
+        for wave in game_config.spawners.waves.iter() {
+            if let Some(name) = wave.archetype.as_deref().filter(|name| ennemy_archetypes.get(name).is_none()) {
+                return Err(format!("the wave at {}s asks for ennemy archetype {}, which is not in {}", wave.at, name, ENNEMY_ARCHETYPES_PATH));
+            }
+        }
+        Ok(ennemy_archetypes)
+    }
+
+    // The clips of assets/animations/player.ron, or the ones of player.png when the file is missing
+    pub fn player_animations(&self) -> Result<AnimationSet<PlayerState>, String> {
+        let path = Path::new(PLAYER_ANIMATIONS_PATH);
+        let animations = if path.exists() { AnimationSet::load(path)? } else { AnimationSet::default() };
+        // Swings land on it
+        if !animations.has_event(PlayerState::Attacking, "hit") {
+            return Err(format!("{}: the Attacking clip needs a \"hit\" event", PLAYER_ANIMATIONS_PATH));
+        }
+        Ok(animations)
+    }
+}
+
+fn parse_resolution(value: &str) -> Result<(f32, f32), String> {
+    let invalid = || format!("expected WxH, e.g. 1280x720, got \"{}\"", value);
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width: u32 = width.parse().map_err(|_| invalid())?;
+    let height: u32 = height.parse().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok((width as f32, height as f32))
+}
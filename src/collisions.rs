@@ -1,17 +1,190 @@
+use std::ops::BitOr;
+
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
+use crate::constants::*;
 use crate::entitypattern::{FacingDirection, EntityBehavior};
+use crate::spatial::SpatialIndex;
+
+
+// Layers a collider can be on, as bits so a mask can hold several of them
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layers(u32);
+
+impl Layers {
+    pub const NONE: Layers = Layers(0);
+    pub const TERRAIN: Layers = Layers(1);
+    pub const STRUCTURE: Layers = Layers(1 << 1);
+    pub const PLAYER: Layers = Layers(1 << 2);
+    pub const ENNEMY: Layers = Layers(1 << 3);
+    pub const TRIGGER: Layers = Layers(1 << 4);
+    pub const HIDDEN: Layers = Layers(1 << 5);
+    pub const PROJECTILE: Layers = Layers(1 << 6);
+
+    pub const fn with(self, other: Layers) -> Layers {
+        Layers(self.0 | other.0)
+    }
+
+    pub const fn without(self, other: Layers) -> Layers {
+        Layers(self.0 & !other.0)
+    }
+
+    pub fn intersects(self, other: Layers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for Layers {
+    type Output = Layers;
+
+    fn bitor(self, other: Layers) -> Layers {
+        self.with(other)
+    }
+}
+
+// The layers a collider is on and the ones it collides with. Two colliders only collide when
+// the mask of each one covers the layers of the other
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CollisionFilter {
+    pub layers: Layers,
+    pub mask: Layers,
+}
 
+impl CollisionFilter {
+    pub const TERRAIN: CollisionFilter = CollisionFilter::new(Layers::TERRAIN, Layers::PLAYER.with(Layers::ENNEMY).with(Layers::PROJECTILE));
+    pub const STRUCTURE: CollisionFilter = CollisionFilter::new(Layers::STRUCTURE, Layers::PLAYER.with(Layers::ENNEMY).with(Layers::PROJECTILE));
+    pub const PLAYER: CollisionFilter = CollisionFilter::new(Layers::PLAYER, Layers::TERRAIN.with(Layers::STRUCTURE).with(Layers::ENNEMY).with(Layers::TRIGGER).with(Layers::PROJECTILE));
+    pub const ENNEMY: CollisionFilter = CollisionFilter::new(Layers::ENNEMY, Layers::TERRAIN.with(Layers::STRUCTURE).with(Layers::PLAYER).with(Layers::ENNEMY).with(Layers::TRIGGER).with(Layers::PROJECTILE));
+    pub const TRIGGER: CollisionFilter = CollisionFilter::new(Layers::TRIGGER, Layers::PLAYER.with(Layers::ENNEMY));
+    // Foliage the player can hide in
+    pub const BUSH: CollisionFilter = CollisionFilter::new(Layers::TRIGGER, Layers::PLAYER);
+    // Projectiles have no collider of their own, nothing runs into them
+    pub const PROJECTILE: CollisionFilter = CollisionFilter::new(Layers::PROJECTILE, Layers::TERRAIN.with(Layers::STRUCTURE).with(Layers::PLAYER).with(Layers::ENNEMY));
+
+    pub const fn new(layers: Layers, mask: Layers) -> Self {
+        CollisionFilter { layers, mask }
+    }
+
+    pub fn interacts_with(&self, other: &CollisionFilter) -> bool {
+        self.mask.intersects(other.layers) && other.mask.intersects(self.layers)
+    }
+
+    // Still in the world, so nothing gets placed on top of it, but nothing collides with it
+    pub fn hidden(self) -> Self {
+        CollisionFilter::new(self.layers | Layers::HIDDEN, Layers::NONE)
+    }
+
+    pub fn ignoring(self, layers: Layers) -> Self {
+        CollisionFilter::new(self.layers, self.mask.without(layers))
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.layers.intersects(Layers::HIDDEN)
+    }
 
-#[derive(Component, Clone)]
+    // Triggers report what overlaps them but never block a move
+    pub fn is_solid(&self) -> bool {
+        !self.layers.intersects(Layers::TRIGGER)
+    }
+}
+
+// Outline of a collider, centered on its position
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shape {
+    Rect { w: f32, h: f32 },
+    Circle { radius: f32 },
+    // Rounded at both ends of its longest side, given by its bounding size
+    Capsule { w: f32, h: f32 },
+}
+
+impl Shape {
+    // Size of the bounding box
+    pub fn size(&self) -> Vec2 {
+        match *self {
+            Shape::Rect { w, h } | Shape::Capsule { w, h } => Vec2::new(w, h),
+            Shape::Circle { radius } => Vec2::splat(radius * 2.),
+        }
+    }
+
+    // Same shape with its outline pushed out by margin on every side
+    pub fn grown(&self, margin: f32) -> Shape {
+        match *self {
+            Shape::Rect { w, h } => Shape::Rect { w: w + margin * 2., h: h + margin * 2. },
+            Shape::Circle { radius } => Shape::Circle { radius: radius + margin },
+            Shape::Capsule { w, h } => Shape::Capsule { w: w + margin * 2., h: h + margin * 2. },
+        }
+    }
+
+    // Every shape is a box, flat for a circle and a capsule, with rounded edges of this radius around it
+    fn core(&self) -> (Vec2, f32) {
+        match *self {
+            Shape::Rect { w, h } => (Vec2::new(w, h) / 2., 0.),
+            Shape::Circle { radius } => (Vec2::ZERO, radius),
+            Shape::Capsule { w, h } => {
+                let radius = w.min(h) / 2.;
+                (Vec2::new(w, h) / 2. - radius, radius)
+            }
+        }
+    }
+}
+
+// Shape of a collider and where it sits relative to the position of its entity
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hitbox {
+    pub shape: Shape,
+    pub offset: Vec2,
+}
+
+impl Hitbox {
+    pub const fn rect(w: f32, h: f32) -> Self {
+        Hitbox { shape: Shape::Rect { w, h }, offset: Vec2::ZERO }
+    }
+
+    pub const fn circle(radius: f32) -> Self {
+        Hitbox { shape: Shape::Circle { radius }, offset: Vec2::ZERO }
+    }
+
+    pub const fn capsule(w: f32, h: f32) -> Self {
+        Hitbox { shape: Shape::Capsule { w, h }, offset: Vec2::ZERO }
+    }
+
+    pub const fn with_offset(self, x: f32, y: f32) -> Self {
+        Hitbox { shape: self.shape, offset: Vec2::new(x, y) }
+    }
+}
+
+// A shape placed in the world, what overlap tests and the resolver work on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Collider {
+    pub center: Vec2,
+    pub shape: Shape,
+}
+
+impl Collider {
+    pub fn new(center: Vec2, shape: Shape) -> Self {
+        Collider { center, shape }
+    }
+
+    // A plain box, centered on x and y
+    pub fn rect(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Collider::new(Vec2::new(x, y), Shape::Rect { w, h })
+    }
+
+    fn moved_by(self, delta: Vec2) -> Self {
+        Collider::new(self.center + delta, self.shape)
+    }
+}
+
+#[derive(Component, Clone, PartialEq)]
 pub struct CollisionComponent {
     x: f32,
     y: f32,
-    w: f32,
-    h: f32,
+    hitbox: Hitbox,
+    filter: CollisionFilter,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Debug)]
 pub enum RelativePosition {
     Left,
     Right,
@@ -20,137 +193,311 @@ pub enum RelativePosition {
 }
 
 impl CollisionComponent {
-    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
-        CollisionComponent { x, y, w, h }
+    pub fn new(x: f32, y: f32, w: f32, h: f32, filter: CollisionFilter) -> Self {
+        CollisionComponent::from_hitbox(x, y, Hitbox::rect(w, h), filter)
+    }
+
+    // x and y are the position of the entity, the hitbox offset is applied on top of it
+    pub fn from_hitbox(x: f32, y: f32, hitbox: Hitbox, filter: CollisionFilter) -> Self {
+        CollisionComponent { x, y, hitbox, filter }
     }
 
     pub fn new_from_component(component: &dyn Collisionable) -> Self {
-        let (x, y, w, h) = component.get_hitbox();
-        CollisionComponent::new(x, y, w, h)
+        let (x, y, _, _) = component.get_hitbox();
+        CollisionComponent::from_hitbox(x, y, Hitbox { shape: component.collision_shape(), offset: Vec2::ZERO }, component.collision_filter())
+    }
+
+    fn center(&self) -> Vec2 {
+        Vec2::new(self.x, self.y) + self.hitbox.offset
     }
 
+    // Bounding box of the shape, centered
     pub fn get_hitbox(&self) -> (f32, f32, f32, f32) {
-        (self.x, self.y, self.w, self.h)
+        let center = self.center();
+        let size = self.hitbox.shape.size();
+        (center.x, center.y, size.x, size.y)
     }
 
+    // Copies the box and the filter, a structure that shows up or hides changes its filter
     pub fn update_hitbox(&mut self, component: &dyn Collisionable) {
-        let (x, y, w, h) = component.get_hitbox();
-        self.x = x;
-        self.y = y;
-        self.w = w;
-        self.h = h;
+        *self = CollisionComponent::new_from_component(component);
     }
 
     pub fn set_pos(&mut self, x: f32, y: f32) {
         self.x = x;
         self.y = y;
     }
+
+    pub fn set_filter(&mut self, filter: CollisionFilter) {
+        self.filter = filter;
+    }
 }
 
 impl Collisionable for CollisionComponent {
     fn get_pos(&self) -> (f32, f32) {
-        (self.x, self.y)
+        (self.center().x, self.center().y)
     }
 
     fn get_hitbox(&self) -> (f32, f32, f32, f32) {
-        (self.x, self.y, self.w, self.h)
+        CollisionComponent::get_hitbox(self)
+    }
+
+    fn collision_filter(&self) -> CollisionFilter {
+        self.filter
+    }
+
+    fn collision_shape(&self) -> Shape {
+        self.hitbox.shape
     }
 }
 
 
 pub trait Collisionable {
     fn get_pos(&self) -> (f32, f32);
+    // Bounding box, centered
     fn get_hitbox(&self) -> (f32, f32, f32, f32);
+    fn collision_filter(&self) -> CollisionFilter;
 
-    fn would_collide(&self, x: f32, y:f32, other: &CollisionComponent) -> bool {
-        let (x1, y1, w1, h1) = (x, y, self.get_hitbox().2, self.get_hitbox().3);
-        let (x2, y2, w2, h2) = other.get_hitbox();
+    fn collision_shape(&self) -> Shape {
+        let (_, _, w, h) = self.get_hitbox();
+        Shape::Rect { w, h }
+    }
 
-        are_overlapping(x1, y1, w1, h1, x2, y2, w2, h2)
-        
+    fn collider_at(&self, x: f32, y: f32) -> Collider {
+        Collider::new(Vec2::new(x, y), self.collision_shape())
     }
 
-    fn would_collide_with(&self, other: &dyn Collisionable) -> bool {
-        let self_pos: (f32, f32) = self.get_pos();
-        self.would_collide(self_pos.0, self_pos.1, &other.get_collision_component())
+    fn collider(&self) -> Collider {
+        let (x, y, _, _) = self.get_hitbox();
+        self.collider_at(x, y)
+    }
+
+    fn can_collide_with(&self, other: &dyn Collisionable) -> bool {
+        self.collision_filter().interacts_with(&other.collision_filter())
+    }
+
+    fn would_collide(&self, x: f32, y:f32, other: &CollisionComponent) -> bool {
+        self.can_collide_with(other) && are_overlapping(self.collider_at(x, y), other.collider())
     }
 
     fn get_collision_component(&self) -> CollisionComponent {
-        let (x, y, w, h) = self.get_hitbox();
-        CollisionComponent::new(x, y, w, h)
+        let (x, y, _, _) = self.get_hitbox();
+        CollisionComponent::from_hitbox(x, y, Hitbox { shape: self.collision_shape(), offset: Vec2::ZERO }, self.collision_filter())
     }
 
     fn get_relative_position(&self, other: &dyn Collisionable) -> Option<RelativePosition> {
-        let (x1, y1, w1, h1) = self.get_hitbox();
-        let (x2, y2, w2, h2) = other.get_hitbox();
-
-        get_relative_position(x1, y1, w1, h1, x2, y2, w2, h2)
+        get_relative_position(self.collider(), other.collider())
     }
     
 }
 
-pub fn get_position_from_center_to_corner(x: f32, y: f32, w: f32, h: f32) -> (f32, f32) {
-    (x - w/2., y - h/2.)
-}
+// Signed distance between two colliders, negative when they overlap, and the direction to move a
+// along to get away from b. Both are rounded boxes, so this is the gap between their inner boxes
+// minus the radii; once the inner boxes overlap, the full shapes are pushed apart along one axis
+pub fn separation(a: Collider, b: Collider) -> (f32, Vec2) {
+    let (half_a, radius_a) = a.shape.core();
+    let (half_b, radius_b) = b.shape.core();
+    let offset = a.center - b.center;
+    let side = Vec2::new(if offset.x < 0. { -1. } else { 1. }, if offset.y < 0. { -1. } else { 1. });
+
+    let gap = (offset.abs() - half_a - half_b).max(Vec2::ZERO);
+    if gap != Vec2::ZERO {
+        let length = gap.length();
+        return (length - radius_a - radius_b, gap * side / length);
+    }
 
-pub fn are_overlapping(x1: f32, y1: f32, w1: f32, h1: f32, 
-                       x2: f32, y2: f32, w2: f32, h2: f32) -> bool {
-    let (x1, y1) = get_position_from_center_to_corner(x1, y1, w1, h1);
-    let (x2, y2) = get_position_from_center_to_corner(x2, y2, w2, h2);
+    let overlap = half_a + half_b + Vec2::splat(radius_a + radius_b) - offset.abs();
+    if overlap.x < overlap.y {
+        (-overlap.x, Vec2::new(side.x, 0.))
+    } else {
+        (-overlap.y, Vec2::new(0., side.y))
+    }
+}
 
-    let x1_min = x1;
-    let x1_max = x1 + w1;
-    let y1_min = y1;
-    let y1_max = y1 + h1;
+// Touching counts
+pub fn are_overlapping(a: Collider, b: Collider) -> bool {
+    separation(a, b).0 <= 0.
+}
 
-    let x2_min = x2;
-    let x2_max = x2 + w2;
-    let y2_min = y2;
-    let y2_max = y2 + h2;
+// Minimum translation vector: the shortest move that takes a out of b, none when they do not overlap or only touch
+pub fn penetration(a: Collider, b: Collider) -> Option<Vec2> {
+    let (distance, normal) = separation(a, b);
+    (distance < 0.).then(|| normal * -distance)
+}
 
-    if x1_max < x2_min || x1_min > x2_max {
-        return false;
+// Swept test of a moving by delta against b: fraction of the move done before the contact and
+// normal of the surface hit. Starting inside is left to penetration
+pub fn sweep(a: Collider, delta: Vec2, b: Collider) -> Option<(f32, Vec2)> {
+    match (a.shape, b.shape) {
+        (Shape::Rect { .. }, Shape::Rect { .. }) => sweep_boxes(a, delta, b),
+        _ => sweep_rounded(a, delta, b),
     }
+}
 
-    if y1_max < y2_min || y1_min > y2_max {
-        return false;
+// Growing b by the half size of a turns it into a ray against a box
+fn sweep_boxes(a: Collider, delta: Vec2, b: Collider) -> Option<(f32, Vec2)> {
+    let origin = a.center;
+    let center = b.center;
+    let half_size = (a.shape.size() + b.shape.size()) / 2.;
+
+    let mut entry = Vec2::splat(f32::NEG_INFINITY);
+    let mut exit = Vec2::splat(f32::INFINITY);
+    for axis in 0..2 {
+        if delta[axis] != 0. {
+            let t1 = (center[axis] - half_size[axis] - origin[axis]) / delta[axis];
+            let t2 = (center[axis] + half_size[axis] - origin[axis]) / delta[axis];
+            entry[axis] = t1.min(t2);
+            exit[axis] = t1.max(t2);
+        } else if (origin[axis] - center[axis]).abs() >= half_size[axis] {
+            // Moving beside the box without ever overlapping it on this axis
+            return None;
+        }
     }
 
-    true
+    let time = entry.max_element();
+    // Grazing a corner is not a contact
+    if !(0. ..=1.).contains(&time) || time >= exit.min_element() {
+        return None;
+    }
+    let normal = if entry.x > entry.y {
+        Vec2::new(-delta.x.signum(), 0.)
+    } else {
+        Vec2::new(0., -delta.y.signum())
+    };
+    Some((time, normal))
 }
 
-pub fn get_relative_position(x1: f32, y1: f32, w1: f32, h1: f32, 
-                             x2: f32, y2: f32, w2: f32, h2: f32) -> Option<RelativePosition> {
-    let (x1, y1) = get_position_from_center_to_corner(x1, y1, w1, h1);
-    let (x2, y2) = get_position_from_center_to_corner(x2, y2, w2, h2);
+// Conservative advancement: the gap cannot close faster than the length of the move,
+// so stepping by the gap never goes through the surface
+fn sweep_rounded(a: Collider, delta: Vec2, b: Collider) -> Option<(f32, Vec2)> {
+    let length = delta.length();
+    if length == 0. {
+        return None;
+    }
+
+    let mut time = 0.;
+    for _ in 0..COLLISION_SWEEP_STEPS {
+        let (distance, normal) = separation(a.moved_by(delta * time), b);
+        if distance < 0. && time == 0. {
+            return None;
+        }
+        if distance <= COLLISION_SKIN {
+            // Already moving away or along the surface, a convex pair will not meet again
+            return (delta.dot(normal) < 0.).then_some((time, normal));
+        }
+        time += distance / length;
+        if time > 1. {
+            return None;
+        }
+    }
+    None
+}
 
-    let x1_min = x1;
-    let x1_max = x1 + w1;
-    let y1_min = y1;
-    let y1_max = y1 + h1;
+// Box covering a hitbox over its whole move, the obstacles a move can meet are all in it
+pub fn swept_area(hitbox: (f32, f32, f32, f32), delta: Vec2) -> (f32, f32, f32, f32) {
+    let (x, y, w, h) = hitbox;
+    (x + delta.x / 2., y + delta.y / 2., w + delta.x.abs() + 2. * COLLISION_SKIN, h + delta.y.abs() + 2. * COLLISION_SKIN)
+}
 
-    let x2_min = x2;
-    let x2_max = x2 + w2;
-    let y2_min = y2;
-    let y2_max = y2 + h2;
+// Where a collider ends up when it tries to move by delta among obstacles: pushed out of what it
+// already overlaps, then moved up to the first contact and slid along the surface with what is left
+pub fn move_and_slide(collider: Collider, delta: Vec2, obstacles: &[&CollisionComponent]) -> Vec2 {
+    let mut position = collider.center;
+    let at = |position: Vec2| Collider::new(position, collider.shape);
+
+    for _ in 0..COLLISION_MAX_ITERATIONS {
+        let deepest = obstacles.iter()
+            .filter_map(|obstacle| penetration(at(position), obstacle.collider()))
+            .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+        match deepest {
+            Some(push) => position += push + push.normalize() * COLLISION_SKIN,
+            None => break,
+        }
+    }
 
-    if x1_max < x2_min {
-        return Some(RelativePosition::Left);
+    let mut remaining = delta;
+    for _ in 0..COLLISION_MAX_ITERATIONS {
+        if remaining == Vec2::ZERO {
+            break;
+        }
+        let first_contact = obstacles.iter()
+            .filter_map(|obstacle| sweep(at(position), remaining, obstacle.collider()))
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        match first_contact {
+            Some((time, normal)) => {
+                position += remaining * time + normal * COLLISION_SKIN;
+                remaining *= 1. - time;
+                remaining -= normal * remaining.dot(normal);
+            }
+            None => {
+                position += remaining;
+                break;
+            }
+        }
     }
+    position
+}
+
+// The colliders around a mover, found through the spatial index and kept when its filter accepts them
+#[derive(SystemParam)]
+pub struct Surroundings<'w, 's> {
+    spatial_index: Res<'w, SpatialIndex>,
+    colliders: Query<'w, 's, &'static CollisionComponent>,
+}
 
-    if x1_min > x2_max {
-        return Some(RelativePosition::Right);
+impl Surroundings<'_, '_> {
+    // The solid colliders a hitbox can meet while moving by delta, itself excluded
+    pub fn obstacles_on_the_way(&self, itself: Entity, hitbox: (f32, f32, f32, f32), filter: CollisionFilter, delta: Vec2) -> Vec<&CollisionComponent> {
+        let (x, y, w, h) = swept_area(hitbox, delta);
+        self.spatial_index.colliders_near(x, y, w, h).into_iter()
+            .filter(|entity| *entity != itself)
+            .filter_map(|entity| self.colliders.get(entity).ok())
+            .filter(|collider| collider.filter.is_solid() && filter.interacts_with(&collider.filter))
+            .collect()
     }
 
-    if y1_max < y2_min {
-        return Some(RelativePosition::Bottom);
+    // First solid collider the filter accepts that a collider moving by delta runs into, itself excluded,
+    // with the fraction of the move done by then. One it already overlaps is hit right away
+    pub fn first_hit(&self, itself: Entity, collider: Collider, filter: CollisionFilter, delta: Vec2) -> Option<(Entity, f32)> {
+        let size = collider.shape.size();
+        let (x, y, w, h) = swept_area((collider.center.x, collider.center.y, size.x, size.y), delta);
+        self.spatial_index.colliders_near(x, y, w, h).into_iter()
+            .filter(|entity| *entity != itself)
+            .filter_map(|entity| self.colliders.get(entity).ok().map(|other| (entity, other)))
+            .filter(|(_, other)| other.filter.is_solid() && filter.interacts_with(&other.filter))
+            .filter_map(|(entity, other)| {
+                if are_overlapping(collider, other.collider()) {
+                    return Some((entity, 0.));
+                }
+                sweep(collider, delta, other.collider()).map(|(time, _)| (entity, time))
+            })
+            .min_by(|(entity, time), (other, other_time)| time.total_cmp(other_time).then(entity.cmp(other)))
     }
 
-    if y1_min > y2_max {
-        return Some(RelativePosition::Top);
+    // First solid collider the filter accepts along the ray, itself excluded
+    pub fn raycast(&self, itself: Entity, filter: CollisionFilter, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<(Entity, f32)> {
+        self.spatial_index.raycast(origin, direction, max_distance, |entity| {
+            entity != itself && self.colliders.get(entity).is_ok_and(|collider| collider.filter.is_solid() && filter.interacts_with(&collider.filter))
+        })
     }
+}
 
-    None
+// Side of b that a is on, none when they overlap. The gap between the shapes says which way to
+// look, the left and right sides win over the top and bottom ones when a is off diagonally
+pub fn get_relative_position(a: Collider, b: Collider) -> Option<RelativePosition> {
+    let (distance, normal) = separation(a, b);
+    if distance <= 0. {
+        return None;
+    }
+    Some(if normal.x < 0. {
+        RelativePosition::Left
+    } else if normal.x > 0. {
+        RelativePosition::Right
+    } else if normal.y < 0. {
+        RelativePosition::Bottom
+    } else {
+        RelativePosition::Top
+    })
 }
 
 pub fn equals(facing_direction: &FacingDirection, relative_position: Option<RelativePosition>) -> bool {
@@ -182,3 +529,125 @@ pub fn update_collisionable_pos<T: EntityBehavior + Component>(
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circle(x: f32, y: f32, radius: f32) -> Collider {
+        Collider::new(Vec2::new(x, y), Shape::Circle { radius })
+    }
+
+    #[test]
+    fn boxes_touching_overlap() {
+        assert!(are_overlapping(Collider::rect(0., 0., 10., 10.), Collider::rect(10., 0., 10., 10.)));
+        assert!(!are_overlapping(Collider::rect(0., 0., 10., 10.), Collider::rect(10.5, 0., 10., 10.)));
+    }
+
+    #[test]
+    fn diagonal_circles_with_overlapping_boxes_do_not_overlap() {
+        let a = circle(0., 0., 5.);
+        let b = circle(8., 8., 5.);
+        assert!(!are_overlapping(a, b));
+        assert_eq!(get_relative_position(a, b), Some(RelativePosition::Left));
+        assert_eq!(get_relative_position(b, a), Some(RelativePosition::Right));
+    }
+
+    #[test]
+    fn diagonal_capsules_with_overlapping_boxes_do_not_overlap() {
+        let a = Collider::new(Vec2::ZERO, Shape::Capsule { w: 20., h: 10. });
+        let b = Collider::new(Vec2::new(16., 9.), Shape::Capsule { w: 20., h: 10. });
+        assert!(!are_overlapping(a, b));
+        assert!(get_relative_position(a, b).is_some());
+    }
+
+    #[test]
+    fn capsule_and_box_overlap_along_the_flat_side() {
+        let capsule = Collider::new(Vec2::ZERO, Shape::Capsule { w: 20., h: 10. });
+        assert!(are_overlapping(capsule, Collider::rect(0., 6., 4., 4.)));
+        assert_eq!(get_relative_position(capsule, Collider::rect(0., 6., 4., 4.)), None);
+    }
+
+    #[test]
+    fn relative_position_is_the_side_of_the_gap() {
+        let a = Collider::rect(0., 0., 10., 10.);
+        assert_eq!(get_relative_position(a, Collider::rect(20., 0., 10., 10.)), Some(RelativePosition::Left));
+        assert_eq!(get_relative_position(a, Collider::rect(0., 20., 10., 10.)), Some(RelativePosition::Bottom));
+        assert_eq!(get_relative_position(a, Collider::rect(0., -20., 10., 10.)), Some(RelativePosition::Top));
+        assert_eq!(get_relative_position(a, Collider::rect(-20., 0., 10., 10.)), Some(RelativePosition::Right));
+    }
+
+    fn obstacle(x: f32, y: f32, w: f32, h: f32) -> CollisionComponent {
+        CollisionComponent::new(x, y, w, h, CollisionFilter::TERRAIN)
+    }
+
+    #[test]
+    fn penetration_is_the_shortest_way_out() {
+        assert_eq!(penetration(Collider::rect(0., 0., 10., 10.), Collider::rect(10., 0., 10., 10.)), None);
+        assert_eq!(penetration(Collider::rect(8., 1., 10., 10.), Collider::rect(0., 0., 10., 10.)), Some(Vec2::new(2., 0.)));
+        assert_eq!(penetration(Collider::rect(0., -7., 10., 10.), Collider::rect(0., 0., 10., 10.)), Some(Vec2::new(0., -3.)));
+        let push = penetration(circle(3., 4., 5.), circle(0., 0., 5.)).unwrap();
+        assert!((push - Vec2::new(3., 4.)).length() < 1e-4, "{:?}", push);
+    }
+
+    #[test]
+    fn sweep_of_a_box_stops_at_the_contact() {
+        let (time, normal) = sweep(Collider::rect(0., 0., 10., 10.), Vec2::new(20., 0.), Collider::rect(25., 0., 10., 10.)).unwrap();
+        assert_eq!((time, normal), (0.75, Vec2::new(-1., 0.)));
+        // Beside the box, past it, and only grazing its corner
+        assert_eq!(sweep(Collider::rect(0., 20., 10., 10.), Vec2::new(50., 0.), Collider::rect(25., 0., 10., 10.)), None);
+        assert_eq!(sweep(Collider::rect(0., 0., 10., 10.), Vec2::new(10., 0.), Collider::rect(25., 0., 10., 10.)), None);
+        assert_eq!(sweep(Collider::rect(0., 0., 10., 10.), Vec2::new(20., 20.), Collider::rect(20., 0., 10., 10.)), None);
+    }
+
+    #[test]
+    fn sweep_starting_inside_is_left_to_penetration() {
+        assert_eq!(sweep(Collider::rect(0., 0., 10., 10.), Vec2::new(5., 0.), Collider::rect(2., 0., 10., 10.)), None);
+        assert_eq!(sweep(circle(0., 0., 5.), Vec2::new(5., 0.), circle(2., 0., 5.)), None);
+    }
+
+    #[test]
+    fn sweep_without_moving_hits_nothing() {
+        assert_eq!(sweep(circle(0., 0., 5.), Vec2::ZERO, circle(20., 0., 5.)), None);
+        assert_eq!(sweep(Collider::rect(0., 0., 10., 10.), Vec2::ZERO, Collider::rect(20., 0., 10., 10.)), None);
+    }
+
+    #[test]
+    fn sweep_of_a_circle_reaches_the_surface() {
+        let (time, normal) = sweep(circle(0., 0., 5.), Vec2::new(20., 0.), Collider::rect(20., 0., 10., 10.)).unwrap();
+        assert!((time * 20. - 10.).abs() <= COLLISION_SKIN, "{}", time);
+        assert_eq!(normal, Vec2::new(-1., 0.));
+        // Moving away from a circle it touches
+        assert_eq!(sweep(circle(0., 0., 5.), Vec2::new(-20., 0.), circle(10., 0., 5.)), None);
+    }
+
+    #[test]
+    fn move_and_slide_moves_freely_without_obstacles() {
+        assert_eq!(move_and_slide(Collider::rect(0., 0., 10., 10.), Vec2::new(3., -4.), &[]), Vec2::new(3., -4.));
+    }
+
+    #[test]
+    fn move_and_slide_slides_along_a_wall() {
+        let wall = obstacle(20., 0., 10., 100.);
+        let position = move_and_slide(Collider::rect(0., 0., 10., 10.), Vec2::new(20., 20.), &[&wall]);
+        assert!((position.x - 10.).abs() <= 2. * COLLISION_SKIN, "{:?}", position);
+        assert!((position.y - 20.).abs() <= 2. * COLLISION_SKIN, "{:?}", position);
+        assert!(!are_overlapping(Collider::new(position, Shape::Rect { w: 10., h: 10. }), wall.collider()));
+    }
+
+    #[test]
+    fn move_and_slide_pushes_out_of_an_overlap_first() {
+        let wall = obstacle(0., 0., 10., 10.);
+        let position = move_and_slide(Collider::rect(8., 0., 10., 10.), Vec2::ZERO, &[&wall]);
+        assert!(position.x >= 10. && position.x <= 10. + 2. * COLLISION_SKIN, "{:?}", position);
+        assert_eq!(position.y, 0.);
+    }
+
+    #[test]
+    fn move_and_slide_stops_in_a_corner() {
+        let right = obstacle(20., 0., 10., 100.);
+        let top = obstacle(0., 20., 100., 10.);
+        let position = move_and_slide(Collider::rect(0., 0., 10., 10.), Vec2::new(30., 30.), &[&right, &top]);
+        assert!((position - Vec2::new(10., 10.)).length() <= 4. * COLLISION_SKIN, "{:?}", position);
+    }
+}
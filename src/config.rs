@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::constants::*;
+
+// Seed and balance values of a game, read from a RON file so a tweak does not need a recompile.
+// Every field is optional in the file, missing ones keep the value from constants.rs.
+// Saves and replays hold a copy of it, the world they rebuild depends on more than the seed
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub seed: u64,
+    pub map_size: f32,
+    pub fullscreen: bool,
+    pub resolution: (f32, f32),
+
+    pub player_health: i32,
+    pub player_damage: i32,
+    pub player_normal_speed: f32,
+    pub player_sprint_speed: f32,
+    pub player_attack_delay: u64,
+    // Speed an ennemy hit by the player is pushed away at, seconds the player cannot be hurt again after a hit
+    pub player_knockback: f32,
+    pub player_invulnerability: f32,
+    // Spent by sprinting, blocking and dodging, regenerated per second once rested
+    pub player_stamina: f32,
+    pub player_stamina_regen: f32,
+
+    pub sanctuary_nb: u32,
+    pub sanctuary_healing: i32,
+
+    // The stats of each kind of ennemy are in assets/ennemies, these scale all of them
+    pub ennemies_number: u32,
+    pub ennemy_damage_multiplier: f32,
+    pub ennemy_aggro_multiplier: f32,
+
+    pub tree_number: u32,
+    pub bush_number: u32,
+    pub graves_number: u32,
+
+    pub spawners: SpawnerConfig,
+}
+
+// Where and how fast ennemies come back once the first ones are summoned
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnerConfig {
+    // Graves turned into spawners, and spawners placed anywhere on top of them
+    pub at_graves: u32,
+    pub generated: u32,
+    // Ennemies alive from a single spawner, and on the whole map, beyond which nothing spawns
+    pub cap: u32,
+    pub max_ennemies: u32,
+    pub cooldown: f32,
+    // A spawner this close to the player stays quiet
+    pub min_player_distance: f32,
+    pub waves: Vec<Wave>,
+}
+
+// A burst of ennemies spread over the spawners, whatever their cap and cooldown
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Wave {
+    // Seconds of play before it comes
+    pub at: f32,
+    pub ennemies: u32,
+    // Every ennemy of the wave is of this archetype, picked from the spawn table otherwise
+    #[serde(default)]
+    pub archetype: Option<String>,
+}
+
+impl Default for SpawnerConfig {
+    fn default() -> Self {
+        SpawnerConfig {
+            at_graves: SPAWNERS_AT_GRAVES,
+            generated: SPAWNERS_GENERATED,
+            cap: SPAWNER_CAP,
+            max_ennemies: SPAWNER_MAX_ENNEMIES,
+            cooldown: SPAWNER_COOLDOWN,
+            min_player_distance: SPAWNER_MIN_PLAYER_DISTANCE,
+            waves: Vec::new(),
+        }
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            seed: DEFAULT_SEED,
+            map_size: MAP_SIZE,
+            fullscreen: true,
+            resolution: (WINDOW_WIDTH, WINDOW_HEIGHT),
+
+            player_health: PLAYER_HEALTH,
+            player_damage: PLAYER_DAMAGE,
+            player_normal_speed: PLAYER_NORMAL_SPEED,
+            player_sprint_speed: PLAYER_SPRINT_SPEED,
+            player_attack_delay: PLAYER_ATTACK_DELAY,
+            player_knockback: PLAYER_KNOCKBACK,
+            player_invulnerability: PLAYER_INVULNERABILITY,
+            player_stamina: PLAYER_STAMINA,
+            player_stamina_regen: PLAYER_STAMINA_REGEN,
+
+            sanctuary_nb: SANCTUARY_NB,
+            sanctuary_healing: SANCTUARY_HEALING,
+
+            ennemies_number: ENNEMIES_NUMBER,
+            ennemy_damage_multiplier: 1.,
+            ennemy_aggro_multiplier: 1.,
+
+            tree_number: TREE_NUMBER,
+            bush_number: BUSH_NUMBER,
+            graves_number: GRAVES_NUMBER,
+
+            spawners: SpawnerConfig::default(),
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn load(path: &Path) -> Result<GameConfig, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read config {}: {}", path.display(), err))?;
+        let config: GameConfig = ron::from_str(&content)
+            .map_err(|err| format!("{}:{}", path.display(), err))?;
+        config.validate().map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(config)
+    }
+
+    // The game of a save or a replay, still shown the way this run asked for
+    pub fn restored(&self, saved: &GameConfig) -> GameConfig {
+        GameConfig {
+            fullscreen: self.fullscreen,
+            resolution: self.resolution,
+            ..saved.clone()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.map_size < MAP_MIN_SIZE {
+            return Err(format!("map_size must be at least {}, got {}", MAP_MIN_SIZE, self.map_size));
+        }
+        if self.resolution.0 <= 0. || self.resolution.1 <= 0. {
+            return Err(format!("resolution must be positive, got {}x{}", self.resolution.0, self.resolution.1));
+        }
+        if self.player_health <= 0 {
+            return Err(format!("player_health must be positive, got {}", self.player_health));
+        }
+        if self.player_damage < 0 || self.sanctuary_healing < 0 {
+            return Err("player_damage and sanctuary_healing cannot be negative".to_string());
+        }
+        if self.player_normal_speed <= 0. || self.player_sprint_speed <= 0. {
+            return Err("every speed must be positive".to_string());
+        }
+        if self.ennemy_damage_multiplier < 0. || self.ennemy_aggro_multiplier <= 0. {
+            return Err(format!("ennemy_damage_multiplier cannot be negative and ennemy_aggro_multiplier must be positive, got {} and {}",
+                self.ennemy_damage_multiplier, self.ennemy_aggro_multiplier));
+        }
+        if self.player_knockback < 0. || self.player_invulnerability < 0. {
+            return Err("player_knockback and player_invulnerability cannot be negative".to_string());
+        }
+        if self.player_stamina <= 0. || self.player_stamina_regen <= 0. {
+            return Err(format!("player_stamina and player_stamina_regen must be positive, got {} and {}",
+                self.player_stamina, self.player_stamina_regen));
+        }
+        // Trees and graves are kept off the edges of the map, each needs a bit of room there
+        let decor_room = ((self.map_size - 64.) / DECOR_MIN_SPACING).floor().max(0.) as u32;
+        if self.tree_number.saturating_add(self.graves_number) > decor_room * decor_room {
+            return Err(format!("tree_number and graves_number cannot add up to more than {} for this map_size, got {} and {}",
+                decor_room * decor_room, self.tree_number, self.graves_number));
+        }
+        let spawners = &self.spawners;
+        if spawners.cooldown <= 0. || spawners.min_player_distance < 0. {
+            return Err(format!("spawners cooldown must be positive and min_player_distance cannot be negative, got {} and {}",
+                spawners.cooldown, spawners.min_player_distance));
+        }
+        if spawners.at_graves > self.graves_number {
+            return Err(format!("spawners at_graves cannot be above graves_number, got {} for {} graves", spawners.at_graves, self.graves_number));
+        }
+        // Generated spawners are placed where sanctuaries can be, each needs a bit of room there
+        let room = ((self.map_size - SANCTUARY_WIDTH) / SPAWNER_MIN_SPACING).floor().max(0.) as u32;
+        if spawners.generated > room * room {
+            return Err(format!("spawners generated cannot be above {} for this map_size, got {}", room * room, spawners.generated));
+        }
+        if spawners.waves.iter().any(|wave| wave.at < 0.) || spawners.waves.windows(2).any(|waves| waves[0].at > waves[1].at) {
+            return Err("spawners waves must come in order, none before 0".to_string());
+        }
+        Ok(())
+    }
+}
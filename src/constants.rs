@@ -1,18 +1,81 @@
 pub const DEFAULT_SEED: u64 = 8000;
+pub const TICK_RATE: f64 = 60.;
+
+// HEADLESS
+pub const HEADLESS_DEFAULT_TICKS: u32 = 3600;
+
+// REPLAY
+pub const REPLAY_DEFAULT_PATH: &str = "last_run.replay";
+pub const REPLAY_CHECKSUM_INTERVAL: u32 = 60;
+
+// CONFIG
+pub const CONFIG_DEFAULT_PATH: &str = "config.ron";
+pub const MAP_MIN_SIZE: f32 = 400.;
+
+// SAVE
+pub const SAVE_PATH: &str = "savegame.ron";
+pub const SAVE_VERSION: u32 = 4;
+
+// ARCHETYPES
+pub const ENNEMY_ARCHETYPES_PATH: &str = "assets/ennemies";
+
+// ANIMATIONS
+pub const PLAYER_ANIMATIONS_PATH: &str = "assets/animations/player.ron";
+
+// SHEETS
+pub const TREES_SHEET: &str = "Background/trees.png";
+pub const BUSHES_SHEET: &str = "Background/bushes.png";
+pub const GRAVES_SHEET: &str = "Background/graves.png";
+pub const SANCTUARY_SHEET: &str = "sanctuary.png";
+pub const BACKGROUND_IMAGE: &str = "Background/background.png";
+pub const TOWER_IMAGE: &str = "tower.png";
+pub const SANCTUARY_MARKER_IMAGE: &str = "UI/s.png";
+pub const BUTTON_IMAGE: &str = "UI/button.png";
+pub const PRESSED_BUTTON_IMAGE: &str = "UI/pressed_button.png";
+
+// COLLISIONS
+pub const SPATIAL_CELL_SIZE: f32 = 64.; // a bit larger than most hitboxes, so a move only looks at a few cells
+pub const COLLISION_SKIN: f32 = 0.01; // gap kept after a contact so the next move does not start inside the obstacle
+pub const COLLISION_MAX_ITERATIONS: usize = 4; // push-outs and slides per move
+pub const COLLISION_SWEEP_STEPS: usize = 16; // steps to reach a rounded obstacle, a move that needs more is let through
+pub const INTERACTION_MARGIN: f32 = 1.; // how close the player has to stand to a structure to use it
+pub const ENNEMY_ROAMING_LOOKAHEAD: f32 = 8.; // a roaming ennemy stays idle rather than walking into a wall this close
+
+// PROJECTILES
+pub const PROJECTILE_RADIUS: f32 = 2.;
+pub const PROJECTILE_LENGTH: f32 = 7.; // drawn as a thin line along its flight
+
+// NAVIGATION
+pub const NAV_CELL_SIZE: f32 = 8.; // half an ennemy, paths squeeze between close obstacles
+pub const NAV_CLEARANCE: f32 = 2.; // extra room kept between a path and the obstacles
+pub const NAV_MAX_EXPANDED_CELLS: usize = 2000; // past this a search stops and leads as close to the goal as it got
+pub const NAV_PATHS_PER_TICK: usize = 4; // paths computed per tick, the other ennemies wait their turn
+pub const NAV_REPATH_DISTANCE: f32 = 16.; // how far the player moves before a chasing ennemy looks for a new path
+pub const NAV_WAYPOINT_RADIUS: f32 = 2.; // distance at which a waypoint counts as reached
 
 // WINDOW
 pub const WINDOW_WIDTH: f32 = 1920.;
 pub const WINDOW_HEIGHT: f32 = 1080.;
 pub const MAP_SIZE: f32 = 1400.;
 pub const CAMERA_DEFAULT_SCALE: f32 = 0.2;
-// pub const CAMERA_MIN_SCALE: f32 = 0.1;
-// pub const CAMERA_MAX_SCALE: f32 = 1.;
+pub const CAMERA_MIN_SCALE: f32 = 0.1;
+pub const CAMERA_MAX_SCALE: f32 = 1.;
+pub const CAMERA_ZOOM_SPEED: f32 = 0.6; // scale per second
+pub const CAMERA_SMOOTHING: f32 = 8.; // the higher, the tighter the camera follows the player
+pub const CAMERA_DEAD_ZONE_WIDTH: f32 = 24.;
+pub const CAMERA_DEAD_ZONE_HEIGHT: f32 = 16.;
+pub const CAMERA_TRAUMA_DECAY: f32 = 1.5; // trauma lost per second
+pub const CAMERA_SHAKE_MAX_OFFSET: f32 = 6.;
+pub const CAMERA_SHAKE_MAX_ANGLE: f32 = 0.05; // radians
+pub const CAMERA_SHAKE_FREQUENCY: f32 = 25.;
+pub const CAMERA_DAMAGE_TRAUMA: f32 = 0.5;
 
 pub const Z_LAYER_BACKGROUND: f32 = 0.;
 pub const Z_LAYER_PLAYER: f32 = 1.;
 pub const Z_LAYER_GUI: f32 = f32::MAX;
 pub const Z_LAYER_STRUCTURES: f32 = 2.;
 pub const Z_LAYER_ENNEMIES: f32 = Z_LAYER_PLAYER;
+pub const Z_LAYER_PROJECTILES: f32 = 1.5;
 
 
 // PLAYER
@@ -20,15 +83,32 @@ pub const PLAYER_SPRITE_SIZE: f32 = 32.;
 pub const PLAYER_SPRITE_SCALE: f32 = 0.5;
 pub const PLAYER_HITBOX_WIDTH: f32 = PLAYER_SPRITE_SIZE * PLAYER_SPRITE_SCALE;
 pub const PLAYER_HITBOX_HEIGHT: f32 = PLAYER_SPRITE_SIZE * PLAYER_SPRITE_SCALE;
-pub const PLAYER_NORMAL_SPEED: f32 = 1.;
-pub const PLAYER_SPRINT_SPEED: f32 = 2.;
+pub const PLAYER_NORMAL_SPEED: f32 = 60.;
+pub const PLAYER_SPRINT_SPEED: f32 = 120.;
 pub const PLAYER_DAMAGE: i32 = 2;
-pub const PLAYER_ATTACK_RANGE: f32 = 5.;
+pub const PLAYER_ATTACK_REACH: f32 = 14.; // from the center of the player to the closest edge of an ennemy
+pub const PLAYER_ATTACK_HALF_ANGLE: f32 = 60.; // degrees on each side of where the player faces, the swing covers that much
+pub const PLAYER_COMBO_WINDOW: f32 = 0.4; // seconds after a swing to chain the next one
+pub const PLAYER_COMBO_DAMAGE: [f32; 3] = [1., 1., 1.5]; // damage multiplier of each hit of the combo, the last one ends it
 pub const PLAYER_ATTACK_DELAY: u64 = 1000;
 pub const PLAYER_HEALTH: i32 = 20;
+pub const PLAYER_WALK_NOISE: f32 = 40.; // how far ennemies hear the player, standing still makes no noise
+pub const PLAYER_SPRINT_NOISE: f32 = 140.;
+pub const PLAYER_ATTACK_NOISE: f32 = 60.;
+pub const PLAYER_SHIELD_HALF_ANGLE: f32 = 70.; // degrees on each side of where the player faces, the shield covers that much
+pub const PLAYER_KNOCKBACK: f32 = 80.; // speed an ennemy hit by the player is pushed away at
+pub const PLAYER_INVULNERABILITY: f32 = 1.; // seconds after a hit during which nothing hurts the player
+pub const PLAYER_STAMINA: f32 = 100.;
+pub const PLAYER_STAMINA_REGEN: f32 = 25.; // per second, once rested
+pub const PLAYER_STAMINA_REGEN_DELAY: f32 = 1.; // seconds without spending any before it comes back
+pub const PLAYER_SPRINT_STAMINA: f32 = 20.; // per second of sprint
+pub const PLAYER_BLOCK_STAMINA: f32 = 25.; // per blow or projectile the shield stops
+pub const PLAYER_DODGE_STAMINA: f32 = 30.;
+pub const PLAYER_DODGE_SPEED: f32 = 200.; // the dodge goes about speed / KNOCKBACK_FRICTION far
+pub const PLAYER_EXHAUSTION_DURATION: f32 = 2.; // seconds without sprint, block or dodge once out of stamina
 
 // STRUCTURES
-pub const SANCTUARY_NB: i32 = 8;
+pub const SANCTUARY_NB: u32 = 8;
 pub const TOWER_HEIGHT: f32 = 128.;
 pub const TOWER_WIDTH: f32 = 67.;
 pub const SANCTUARY_HEIGHT: f32 = 75.;
@@ -36,28 +116,61 @@ pub const SANCTUARY_WIDTH: f32 = 96.;
 pub const SANCTUARY_HEALING: i32 = 5;
 
 // ENNEMIES
-pub const ENNEMIES_NUMBER: i32 = 60;
+pub const ENNEMIES_NUMBER: u32 = 60;
+pub const ENNEMY_HEALTH: i32 = 10;
+pub const ENNEMY_DAMAGE: i32 = 5;
 pub const ENNEMY_SPRITE_SIZE: f32 = 32.;
 pub const ENNEMY_SPRITE_SCALE: f32 = 0.5;
 pub const ENNEMY_HITBOX_WIDTH: f32 = ENNEMY_SPRITE_SIZE * ENNEMY_SPRITE_SCALE;
 pub const ENNEMY_HITBOX_HEIGHT: f32 = ENNEMY_SPRITE_SIZE * ENNEMY_SPRITE_SCALE;
-pub const ENNEMY_SPRINT_SPEED: f32 = 1.3;
-pub const ENNEMY_NORMAL_SPEED: f32 = 0.6;
+pub const ENNEMY_SPRINT_SPEED: f32 = 78.;
+pub const ENNEMY_NORMAL_SPEED: f32 = 36.;
 pub const ENNEMY_ATTACK_SPEED: f32 = 0.4;
 pub const ENNEMY_ATTACK_RANGE: f32 = 5.;
-pub const ENNEMY_AGGRO_DISTANCE: f32 = 120.;
+pub const ENNEMY_AGGRO_DISTANCE: f32 = 120.; // how far an ennemy sees
+pub const ENNEMY_SIGHT_HALF_ANGLE: f32 = 60.; // degrees on each side of where it faces
+pub const ENNEMY_SEARCH_RADIUS: f32 = 8.; // close enough to where the player was last seen to look around
+pub const ENNEMY_SEARCH_DURATION: f32 = 3.; // seconds spent looking around before roaming again
+pub const ENNEMY_SEARCH_TURN_DELAY: f32 = 0.5;
+pub const ENNEMY_RANGED_DISTANCE_MARGIN: f32 = 12.; // a ranged ennemy holds its ground this close to the distance it keeps
 pub const ENNEMY_DEFENCE_RATIO: f32 = 0.2;
+pub const ENNEMY_ROAMING_MIN_DURATION: f32 = 0.4;
+pub const ENNEMY_ROAMING_MAX_DURATION: f32 = 0.8;
+pub const ENNEMY_KNOCKBACK: f32 = 100.; // speed the player is pushed away at by a blow or a projectile
+pub const ENNEMY_INVULNERABILITY: f32 = 0.3; // shorter than a swing, or the combos would miss
+
+// KNOCKBACK
+pub const KNOCKBACK_FRICTION: f32 = 10.; // share of the push lost per second, it goes about speed / friction far
+pub const KNOCKBACK_MIN_SPEED: f32 = 5.; // slower than this the push is over
+pub const INVULNERABILITY_FLICKER_PERIOD: f32 = 0.1; // seconds drawn, then as long faded
+pub const INVULNERABILITY_FLICKER_ALPHA: f32 = 0.2;
+
+// SPAWNERS
+pub const SPAWNERS_AT_GRAVES: u32 = 6;
+pub const SPAWNERS_GENERATED: u32 = 2;
+pub const SPAWNER_CAP: u32 = 3;
+pub const SPAWNER_MAX_ENNEMIES: u32 = 80;
+pub const SPAWNER_COOLDOWN: f32 = 20.; // seconds between two ennemies of a spawner
+pub const SPAWNER_MIN_PLAYER_DISTANCE: f32 = 250.;
+pub const SPAWNER_SPREAD: f32 = 12.; // how far from its spawn point an ennemy of a wave can appear
+pub const SPAWNER_MARGIN: f32 = 4.; // room left between a grave and the ennemies coming out of it
+pub const SPAWNER_PLACEMENT_ATTEMPTS: u32 = 20; // random spots tried for a generated spawner before giving up on it
+pub const SPAWNER_MIN_SPACING: f32 = 100.; // side of the square of map each generated spawner needs
 
 // OTHERS
 pub const TREE_HEIGHT: f32 = 160.;
 pub const TREE_WIDTH: f32 = 128.;
 pub const TREE_TRANSPARENCY: f32 = 0.6;
-pub const TREE_NUMBER: i32 = 100;
+pub const TREE_NUMBER: u32 = 100;
 pub const BUSH_HEIGHT: f32 = 50.;
 pub const BUSH_WIDTH: f32 = 55.;
 pub const BUSH_TRANSPARENCY: f32 = 0.6;
-pub const BUSH_NUMBER: i32 = 100;
-pub const GRAVES_NUMBER: i32 = 25;
+pub const BUSH_NUMBER: u32 = 100;
+pub const GRAVE_HEIGHT: f32 = 64.;
+pub const GRAVE_WIDTH: f32 = 64.;
+pub const GRAVES_NUMBER: u32 = 25;
+pub const DECOR_PLACEMENT_ATTEMPTS: u32 = 20; // random spots tried for a tree or a grave before giving up on it
+pub const DECOR_MIN_SPACING: f32 = 48.; // side of the square of map each tree or grave needs
 
 
 // RNG OFFSETS
@@ -67,5 +180,8 @@ pub const OFFSET_GRAVE: u64 = 2000;
 pub const OFFSET_ENNEMY: u64 = 3000;
 pub const OFFSET_SANCTUARY: u64 = 4000;
 pub const OFFSET_TOWER: u64 = 5000;
+pub const OFFSET_ENNEMY_AI: u64 = 6000;
+pub const OFFSET_SANCTUARY_REVEAL: u64 = 7000;
+pub const OFFSET_SPAWNER: u64 = 9000;
 
 
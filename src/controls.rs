@@ -0,0 +1,117 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use crate::replay::Replay;
+use crate::{GameState, TickSet};
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Controls>()
+            .init_resource::<PendingPresses>()
+            .add_systems(PreUpdate, latch_presses.after(InputSystem).run_if(in_state(GameState::Playing)))
+            .add_systems(FixedUpdate, sample_controls.in_set(TickSet::Input)
+                                                        .run_if(in_state(GameState::Playing))
+                                                        .run_if(not(resource_exists::<Replay>())));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Control {
+    Up,
+    Down,
+    Left,
+    Right,
+    Sprint,
+    Block,
+    Hide,
+    Interact,
+    Dodge,
+}
+
+impl Control {
+    const ALL: [Control; 9] = [
+        Control::Up,
+        Control::Down,
+        Control::Left,
+        Control::Right,
+        Control::Sprint,
+        Control::Block,
+        Control::Hide,
+        Control::Interact,
+        Control::Dodge,
+    ];
+
+    fn key(&self) -> KeyCode {
+        match self {
+            Control::Up => KeyCode::Up,
+            Control::Down => KeyCode::Down,
+            Control::Left => KeyCode::Left,
+            Control::Right => KeyCode::Right,
+            Control::Sprint => KeyCode::ShiftLeft,
+            Control::Block => KeyCode::E,
+            Control::Hide => KeyCode::A,
+            Control::Interact => KeyCode::Space,
+            Control::Dodge => KeyCode::ControlLeft,
+        }
+    }
+
+    fn bit(&self) -> u16 {
+        1 << *self as u16
+    }
+}
+
+// Keyboard state seen by gameplay during one tick
+#[derive(Resource, Default, Clone, Copy, PartialEq)]
+pub struct Controls {
+    pressed: u16,
+    just_pressed: u16,
+}
+
+impl Controls {
+    pub fn pressed(&self, control: Control) -> bool {
+        self.pressed & control.bit() != 0
+    }
+
+    pub fn just_pressed(&self, control: Control) -> bool {
+        self.just_pressed & control.bit() != 0
+    }
+
+    pub fn from_bits(pressed: u16, just_pressed: u16) -> Self {
+        Controls { pressed, just_pressed }
+    }
+
+    pub fn bits(&self) -> (u16, u16) {
+        (self.pressed, self.just_pressed)
+    }
+}
+
+// Presses seen since the last tick, so a tap shorter than a tick is not lost
+#[derive(Resource, Default)]
+struct PendingPresses(u16);
+
+fn latch_presses(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut pending: ResMut<PendingPresses>,
+) {
+    for control in Control::ALL {
+        if keyboard_input.just_pressed(control.key()) {
+            pending.0 |= control.bit();
+        }
+    }
+}
+
+fn sample_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut pending: ResMut<PendingPresses>,
+    mut controls: ResMut<Controls>,
+) {
+    let mut pressed = 0;
+    for control in Control::ALL {
+        if keyboard_input.pressed(control.key()) {
+            pressed |= control.bit();
+        }
+    }
+    *controls = Controls { pressed, just_pressed: pending.0 };
+    pending.0 = 0;
+}
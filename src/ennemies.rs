@@ -1,22 +1,41 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
 use bevy::prelude::*;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::GameConfig;
-use crate::GameState;
+use crate::{GameState, TickSet};
 use crate::collisions;
 use crate::constants::*;
 use crate::collisions::*;
 use crate::entitypattern::EntityBehavior;
 use crate::entitypattern::EntityPatern;
 use crate::entitypattern::FacingDirection;
+use crate::entitypattern::*;
+use crate::headless::*;
 use crate::player::*;
-
-#[derive(Clone, Copy, PartialEq)]
+use crate::rng::GameRng;
+use crate::save::EnnemySave;
+use crate::config::GameConfig;
+use crate::animation::*;
+use crate::archetypes::{EnnemyArchetype, EnnemyArchetypes};
+use crate::atlases::AtlasRegistry;
+use crate::camera::CameraShake;
+use crate::knockback::{apply_hit_reaction, HitReaction};
+use crate::navigation::NavGrid;
+use crate::projectiles::{spawn_projectile, Projectile};
+use crate::spatial::SpatialIndex;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum EnnemyState {
     Loading,
     Idle,
     Roaming,
     Chasing,
+    // Going where the player was last seen, then looking around
+    Searching,
     Damaged,
     Attacking,
     Blocking,
@@ -25,20 +44,30 @@ pub enum EnnemyState {
     // add more states later
 }
 
+impl AnimationState for EnnemyState {
+    const ALL: &'static [EnnemyState] = &[EnnemyState::Loading, EnnemyState::Idle, EnnemyState::Roaming, EnnemyState::Chasing, EnnemyState::Searching,
+        EnnemyState::Damaged, EnnemyState::Attacking, EnnemyState::Blocking, EnnemyState::Dying, EnnemyState::Dead];
+}
+
 pub struct EnnemyPlugin;
 
 impl Plugin for EnnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnExit(GameState::Menu), summon_ennemies)
-        .add_systems(OnExit(GameState::Loading), summon_ennemies)
-            .add_systems(Update, (game_ready.run_if(run_once()),
-                                                    update_ennemy_position, 
+        app.add_systems(OnExit(GameState::Loading), summon_ennemies)
+            .add_systems(FixedUpdate, (game_ready.run_if(run_once()),
+                                                    save_previous_pos::<Ennemy>,
+                                                    state_speed_update,
+                                                    plan_ennemy_paths,
+                                                    ennemy_aggro_detection,
+                                                    separate_ennemies,
+                                                    apply_hit_reaction::<Ennemy>,
                                                     update_ennemy_hitbox,
+                                                    update_ennemy_animation,
                                                     ennemy_attack, 
-                                                    despawn_on_death,
-                                                    ennemy_aggro_detection,
-                                                    state_speed_update,
-                                                    update_ennemy_sprite,
+                                                    ennemy_shoot,
+                                                    despawn_on_death).chain().in_set(TickSet::Ennemies).run_if(in_state(GameState::Playing)))
+            .add_systems(Update, (update_ennemy_position, 
+                                                    update_ennemy_sprite.run_if(is_rendering),
                                                     change_sprite_orientation).run_if(in_state(GameState::Playing)));  
     }
 }
@@ -46,135 +75,241 @@ impl Plugin for EnnemyPlugin {
 
 #[derive(Component)]
 pub struct Ennemy {
+    id: u32,
     self_entity: EntityPatern,
     current_speed: f32,
-    direction_counter: i32,
+    direction_timer: f32,
     state: EnnemyState,
-    attack: i32,
-    defense_ratio: f32, // chance to block an attack
+    archetype: Arc<EnnemyArchetype>,
+    rng: StdRng,
+    // Spawner it came out of, none for the ones summoned at the start
+    spawner: Option<u32>,
+
+    // Waypoints left towards the player, and where the player stood when they were computed
+    path: Vec<Vec2>,
+    path_goal: Option<Vec2>,
+    ticks_waiting_for_path: u32,
+
+    // Where the player was last seen or heard, and how long it has been looking around there
+    last_seen: Option<Vec2>,
+    search_time: f32,
+
+    // Seconds before a ranged ennemy can shoot again
+    reload_time_left: f32,
+}
 
-    roaming_frame_counter: usize,
-    roaming_frame_time: f32,
+impl Ennemy {
 
-    chasing_frame_counter: usize,
-    chasing_frame_time: f32,
+    pub fn new(id: u32, x: f32, y: f32, archetype: &Arc<EnnemyArchetype>, rng: StdRng) -> Self {
+        Self {
+            id,
+            self_entity: EntityPatern::new(x, y, archetype.hitbox.0, archetype.hitbox.1, archetype.health),
+            current_speed: archetype.normal_speed,
+            direction_timer: 0.,
+            state: EnnemyState::Loading,
+            archetype: Arc::clone(archetype),
+            rng,
+            spawner: None,
 
-    damaged_frame_counter: usize,
-    damaged_frame_time: f32,
+            path: Vec::new(),
+            path_goal: None,
+            ticks_waiting_for_path: 0,
 
-    attacking_frame_counter: usize,
-    attacking_frame_time: f32,
-    attacking_has_hit: bool,
+            last_seen: None,
+            search_time: 0.,
 
-    blocking_frame_counter: usize,
-    blocking_frame_time: f32,
+            reload_time_left: 0.,
+        }
+    }
 
-    dying_frame_counter: usize,
-    dying_frame_time: f32,
+    // Comes out of a spawner in the middle of the game, ready to roam
+    pub fn spawned(id: u32, x: f32, y: f32, archetype: &Arc<EnnemyArchetype>, rng: StdRng, spawner: u32) -> Self {
+        let mut ennemy = Ennemy::new(id, x, y, archetype, rng);
+        ennemy.state = EnnemyState::Roaming;
+        ennemy.spawner = Some(spawner);
+        ennemy
+    }
 
-}
+    pub fn spawner(&self) -> Option<u32> {
+        self.spawner
+    }
 
-impl Ennemy {
+    // An obstacle right in front, so close that walking that way would stop at once
+    fn is_blocked_towards(&self, itself: Entity, direction: &FacingDirection, surroundings: &Surroundings) -> bool {
+        let (x, y, width, height) = self.get_hitbox();
+        let reach = width.max(height) / 2. + ENNEMY_ROAMING_LOOKAHEAD;
+        surroundings.raycast(itself, self.collision_filter(), Vec2::new(x, y), direction.to_vec2(), reach).is_some()
+    }
 
-    pub fn new(x: f32, y: f32, health: i32, attack: i32, defense_ratio: f32) -> Self {
-        Self {
-            self_entity: EntityPatern::new(x, y, ENNEMY_HITBOX_WIDTH, ENNEMY_HITBOX_HEIGHT, health),
-            current_speed: ENNEMY_NORMAL_SPEED,
-            direction_counter: 0,
-            state: EnnemyState::Loading,
-            attack,
-            defense_ratio,
+    // Moves as far as the obstacles let it, sliding along them, and stays inside the map
+    pub fn move_in_direction(
+        &mut self, itself: Entity,
+        direction: &FacingDirection, 
+        amount: f32, 
+        surroundings: &Surroundings,
+        map_size: f32,
+    ) -> bool {
+        let delta = match direction {
+            FacingDirection::Up => Vec2::new(0., amount),
+            FacingDirection::Down => Vec2::new(0., -amount),
+            FacingDirection::Left => Vec2::new(-amount, 0.),
+            FacingDirection::Right => Vec2::new(amount, 0.),
+            FacingDirection::TopLeft => Vec2::new(-amount, amount),
+            FacingDirection::TopRight => Vec2::new(amount, amount),
+            FacingDirection::BottomLeft => Vec2::new(-amount, -amount),
+            FacingDirection::BottomRight => Vec2::new(amount, -amount),
+        };
 
-            roaming_frame_counter: 0,
-            roaming_frame_time: 0.,
+        if !self.move_by(itself, delta, self.collision_filter(), surroundings, map_size) {
+            return false;
+        }
+        self.set_facing_direction(*direction);
+        true
+    }
 
-            chasing_frame_counter: 0,
-            chasing_frame_time: 0.,
+    fn move_by(&mut self, itself: Entity, delta: Vec2, filter: CollisionFilter, surroundings: &Surroundings, map_size: f32) -> bool {
+        let hitbox = self.get_hitbox();
+        let obstacles = surroundings.obstacles_on_the_way(itself, hitbox, filter, delta);
+        let map_limit = Vec2::new(map_size / 2. - self.archetype.hitbox.0 / 2., map_size / 2. - self.archetype.hitbox.1 / 2.);
+        let position = move_and_slide(self.collider(), delta, &obstacles).clamp(-map_limit, map_limit);
 
-            damaged_frame_counter: 0,
-            damaged_frame_time: 0.,
+        if position == Vec2::new(hitbox.0, hitbox.1) {
+            return false;
+        }
+        self.set_x(position.x);
+        self.set_y(position.y);
+        true
+    }
 
-            attacking_frame_counter: 0,
-            attacking_frame_time: 0.,
-            attacking_has_hit: false,
 
-            blocking_frame_counter: 0,
-            blocking_frame_time: 0.,
+    // Where a path has to lead: the player while chasing, where it was last seen while searching
+    fn destination(&self, player_position: Vec2) -> Option<Vec2> {
+        match self.state {
+            EnnemyState::Chasing => Some(player_position),
+            EnnemyState::Searching => self.last_seen,
+            _ => None,
+        }
+    }
 
-            dying_frame_counter: 0,
-            dying_frame_time: 0.,
+    fn needs_path(&self, destination: Vec2) -> bool {
+        !self.path_goal.is_some_and(|goal| goal.distance(destination) <= NAV_REPATH_DISTANCE)
+    }
 
+    // No path means heading straight for the destination
+    fn set_path(&mut self, path: Vec<Vec2>, goal: Vec2) {
+        self.path = path;
+        self.path_goal = Some(goal);
+        self.ticks_waiting_for_path = 0;
+    }
+
+    fn forget_path(&mut self) {
+        self.path.clear();
+        self.path_goal = None;
+        self.ticks_waiting_for_path = 0;
+    }
+
+    // Next waypoint not reached yet, or the destination once the path is done
+    fn next_waypoint(&mut self, destination: Vec2) -> Vec2 {
+        let position = Vec2::new(self.x(), self.y());
+        let reached = self.path.iter().take_while(|waypoint| waypoint.distance(position) <= NAV_WAYPOINT_RADIUS).count();
+        self.path.drain(..reached);
+        self.path.first().copied().unwrap_or(destination)
+    }
+
+    // In front of it, close enough, and no tree, grave or structure in between
+    fn can_see(&self, itself: Entity, target: Vec2, surroundings: &Surroundings) -> bool {
+        let position = Vec2::new(self.x(), self.y());
+        let to_target = target - position;
+        let distance = to_target.length();
+        if distance >= self.archetype.aggro_distance {
+            return false;
+        }
+        // Right next to it, it notices the player whichever way it faces
+        let facing = self.facing_direction().unwrap_or(FacingDirection::Down).to_vec2();
+        if distance > self.archetype.hitbox.0 && facing.dot(to_target / distance) < ENNEMY_SIGHT_HALF_ANGLE.to_radians().cos() {
+            return false;
         }
+        // Ni les autres ennemis ni le joueur ne cachent la vue
+        let filter = self.collision_filter().ignoring(Layers::PLAYER.with(Layers::ENNEMY));
+        surroundings.raycast(itself, filter, position, to_target, distance).is_none()
     }
 
-    fn can_move(
-        &self, direction: &FacingDirection, 
-        amount: f32, 
-        collision_query: &Query<&CollisionComponent, Without<Ennemy>>
-    ) -> bool {
-        let (x, y) = match direction {
-            FacingDirection::Up => (self.x(), self.y() + amount),
-            FacingDirection::Down => (self.x(), self.y() - amount),
-            FacingDirection::Left => (self.x() - amount, self.y()),
-            FacingDirection::Right => (self.x() + amount, self.y()),
-            FacingDirection::TopLeft => (self.x() - amount, self.y() + amount),
-            FacingDirection::TopRight => (self.x() + amount, self.y() + amount),
-            FacingDirection::BottomLeft => (self.x() - amount, self.y() - amount),
-            FacingDirection::BottomRight => (self.x() + amount, self.y() - amount),
-        };
-    
-        for collision in collision_query.iter() {
-            if self.would_collide(x, y, collision) {
-                return false;
+    fn chase_player(&mut self, itself: Entity, player: &Player, surroundings: &Surroundings, delta: f32, map_size: f32) {
+        if !self.is_attacking() { self.state = EnnemyState::Chasing;}
+        let (player_x, player_y) = player.get_pos();
+        self.last_seen = Some(Vec2::new(player_x, player_y));
+        self.search_time = 0.;
+        if let Some(preferred_distance) = self.archetype.ranged.as_ref().map(|ranged| ranged.preferred_distance) {
+            self.keep_distance(itself, Vec2::new(player_x, player_y), preferred_distance, surroundings, delta, map_size);
+            return;
+        }
+        let waypoint = self.next_waypoint(Vec2::new(player_x, player_y));
+        self.walk_towards(itself, waypoint, surroundings, delta, map_size);
+    }
+
+    // A ranged ennemy comes within shooting distance, backs away when the player gets too close,
+    // and otherwise stands still facing the player
+    fn keep_distance(&mut self, itself: Entity, player_position: Vec2, preferred_distance: f32, surroundings: &Surroundings, delta: f32, map_size: f32) {
+        let position = Vec2::new(self.x(), self.y());
+        let distance = position.distance(player_position);
+        if !self.is_attacking() {
+            if distance > preferred_distance + ENNEMY_RANGED_DISTANCE_MARGIN {
+                let waypoint = self.next_waypoint(player_position);
+                self.walk_towards(itself, waypoint, surroundings, delta, map_size);
+                return;
+            }
+            if distance < preferred_distance - ENNEMY_RANGED_DISTANCE_MARGIN {
+                self.walk_towards(itself, position * 2. - player_position, surroundings, delta, map_size);
             }
         }
-    
-        match direction {
-            FacingDirection::Up => y < MAP_SIZE as f32 / 2. - ENNEMY_HITBOX_HEIGHT / 2.,
-            FacingDirection::Down => y > -MAP_SIZE as f32 / 2. + ENNEMY_HITBOX_HEIGHT / 2.,
-            FacingDirection::Left => x > -MAP_SIZE as f32 / 2. + ENNEMY_HITBOX_WIDTH / 2.,
-            FacingDirection::Right => x < MAP_SIZE as f32 / 2. - ENNEMY_HITBOX_WIDTH / 2.,
-            FacingDirection::TopLeft => {
-                y < MAP_SIZE as f32 / 2. - ENNEMY_HITBOX_HEIGHT / 2. && x > -MAP_SIZE as f32 / 2. + ENNEMY_HITBOX_WIDTH / 2.
-            },
-            FacingDirection::TopRight => {
-                y < MAP_SIZE as f32 / 2. - ENNEMY_HITBOX_HEIGHT / 2. && x < MAP_SIZE as f32 / 2. - ENNEMY_HITBOX_WIDTH / 2.
-            },
-            FacingDirection::BottomLeft => {
-                y > -MAP_SIZE as f32 / 2. + ENNEMY_HITBOX_HEIGHT / 2. && x > -MAP_SIZE as f32 / 2. + ENNEMY_HITBOX_WIDTH / 2.
-            },
-            FacingDirection::BottomRight => {
-                y > -MAP_SIZE as f32 / 2. + ENNEMY_HITBOX_HEIGHT / 2. && x < MAP_SIZE as f32 / 2. - ENNEMY_HITBOX_WIDTH / 2.
-            },
+        // Il recule sans quitter le joueur des yeux
+        if let Some(direction) = FacingDirection::from_vec2(player_position - position) {
+            self.set_facing_direction(direction);
         }
     }
-    
-    pub fn move_in_direction(
-        &mut self, direction: &FacingDirection, 
-        amount: f32, 
-        collision_query: &Query<&CollisionComponent, Without<Ennemy>>
-    ) -> bool {
-        if self.can_move(&direction, amount, collision_query) {
-            match direction {
-                FacingDirection::Up => {self.add_y(amount); self.set_facing_direction(FacingDirection::Up)},
-                FacingDirection::Down => {self.add_y(-amount); self.set_facing_direction(FacingDirection::Down)},
-                FacingDirection::Left => {self.add_x(-amount); self.set_facing_direction(FacingDirection::Left)},
-                FacingDirection::Right => {self.add_x(amount); self.set_facing_direction(FacingDirection::Right)},
-                FacingDirection::TopLeft => {self.add_x(-amount); self.add_y(amount); self.set_facing_direction(FacingDirection::TopLeft)},
-                FacingDirection::TopRight => {self.add_x(amount); self.add_y(amount); self.set_facing_direction(FacingDirection::TopRight)},
-                FacingDirection::BottomLeft => {self.add_x(-amount); self.add_y(-amount); self.set_facing_direction(FacingDirection::BottomLeft)},
-                FacingDirection::BottomRight => {self.add_x(amount); self.add_y(-amount); self.set_facing_direction(FacingDirection::BottomRight)},
-            }
-            true
-        } else {
-            false
+
+    fn is_ranged(&self) -> bool {
+        self.archetype.ranged.is_some()
+    }
+
+    // Heads for where the player was last seen or heard, then looks around there for a while
+    fn search(&mut self, itself: Entity, surroundings: &Surroundings, delta: f32, map_size: f32) {
+        let Some(spot) = self.last_seen else {
+            return;
+        };
+        if !self.is_attacking() { self.state = EnnemyState::Searching; }
+        let waypoint = self.next_waypoint(spot);
+        if Vec2::new(self.x(), self.y()).distance(spot) > ENNEMY_SEARCH_RADIUS
+            && self.walk_towards(itself, waypoint, surroundings, delta, map_size) {
+            return;
+        }
+
+        // Arrivé, ou bloqué en chemin : il regarde autour de lui
+        self.direction_timer -= delta;
+        if self.direction_timer <= 0. {
+            let direction = self.facing_direction().unwrap_or(FacingDirection::Down);
+            self.set_facing_direction(direction.clockwise());
+            self.direction_timer = ENNEMY_SEARCH_TURN_DELAY;
+        }
+        self.search_time += delta;
+        if self.search_time >= ENNEMY_SEARCH_DURATION {
+            self.last_seen = None;
+            self.search_time = 0.;
+            self.direction_timer = 0.;
+            self.forget_path();
+            self.state = EnnemyState::Idle;
         }
     }
 
+    // Heard the player: goes to see what made the noise
+    fn investigate(&mut self, itself: Entity, noise: Vec2, surroundings: &Surroundings, delta: f32, map_size: f32) {
+        self.last_seen = Some(noise);
+        self.search_time = 0.;
+        self.search(itself, surroundings, delta, map_size);
+    }
 
-    fn chase_player(&mut self, player: &Player, collision_query: &Query<&CollisionComponent, Without<Ennemy>>) {
-        if !self.is_attacking() { self.state = EnnemyState::Chasing;}
-        let (x, y) = player.get_pos();
+    // One step towards the target, false when it could not move at all
+    fn walk_towards(&mut self, itself: Entity, Vec2 { x, y }: Vec2, surroundings: &Surroundings, delta: f32, map_size: f32) -> bool {
         let dx = x - self.x();  // Difference in x positions
         let dy = y - self.y();  // Difference in y positions
     
@@ -186,7 +321,8 @@ impl Ennemy {
     
         let mut facing_direction: Option<FacingDirection> = None;
     
-        let new_x = self.x() + dx * self.current_speed;
+        let step = self.current_speed * delta;
+        let new_x = self.x() + dx * step;
 
         if new_x < self.x() {
             facing_direction = Some(FacingDirection::Left);
@@ -194,7 +330,7 @@ impl Ennemy {
             facing_direction = Some(FacingDirection::Right);
         }
 
-        let new_y = self.y() + dy * self.current_speed;
+        let new_y = self.y() + dy * step;
 
         if new_y < self.y() {
             if let Some(direction) = facing_direction {
@@ -217,19 +353,19 @@ impl Ennemy {
                 facing_direction = Some(FacingDirection::Up);
             }
         }
-        if let Some(direction) = facing_direction {
-            self.move_in_direction(&direction, self.current_speed, collision_query);
+        match facing_direction {
+            Some(direction) => self.move_in_direction(itself, &direction, step, surroundings, map_size),
+            None => false,
         }
     }
     
 
-    fn roaming(&mut self, collision_query: &Query<&CollisionComponent, Without<Ennemy>>) {
+    fn roaming(&mut self, itself: Entity, surroundings: &Surroundings, delta: f32, map_size: f32) {
+        self.forget_path();
         let new_direction: Option<FacingDirection>;
-        if self.direction_counter <= 0 {
+        if self.direction_timer <= 0. {
             // Choisir une nouvelle direction
-            let mut rng = rand::thread_rng();
-
-            let direction = rng.gen_range(0..17);
+            let direction = self.rng.gen_range(0..17);
             new_direction = Some(match direction {
                 0 => FacingDirection::Up,
                 1 => FacingDirection::Down,
@@ -243,19 +379,51 @@ impl Ennemy {
             });
             if direction < 8 {
                 self.state = EnnemyState::Roaming;
+                // Pas la peine de partir vers un obstacle collé devant soi
+                if let Some(ref direction) = new_direction {
+                    if self.is_blocked_towards(itself, direction, surroundings) {
+                        self.state = EnnemyState::Idle;
+                    }
+                }
             }
-            self.direction_counter = rng.gen_range(25..50); // changer de direction après 50 à 100 itérations
+            self.direction_timer = self.rng.gen_range(ENNEMY_ROAMING_MIN_DURATION..ENNEMY_ROAMING_MAX_DURATION); // changer de direction après 0.4 à 0.8 secondes
         }
         else {
             new_direction = self.facing_direction().clone();
         }
         if !self.is_idle() {
             if let Some(ref direction) = new_direction{
-                self.move_in_direction(direction, self.current_speed, collision_query);
+                self.move_in_direction(itself, direction, self.current_speed * delta, surroundings, map_size);
             }
         }
 
-        self.direction_counter -= 1;
+        self.direction_timer -= delta;
+    }
+
+    pub fn health(&self) -> i32 {
+        self.self_entity.health()
+    }
+
+    pub fn to_save(&self) -> EnnemySave {
+        EnnemySave {
+            id: self.id,
+            x: self.x(),
+            y: self.y(),
+            health: self.health(),
+            state: self.state,
+            facing_direction: self.facing_direction(),
+            archetype: self.archetype.name.clone(),
+            spawner: self.spawner,
+        }
+    }
+
+    pub fn from_save(save: &EnnemySave, archetype: &Arc<EnnemyArchetype>, rng: StdRng) -> Self {
+        let mut ennemy = Ennemy::new(save.id, save.x, save.y, archetype, rng);
+        ennemy.self_entity.set_health(save.health);
+        ennemy.state = save.state;
+        ennemy.self_entity.set_facing(save.facing_direction);
+        ennemy.spawner = save.spawner;
+        ennemy
     }
 
     fn is_taking_damage(&self) -> bool {
@@ -299,24 +467,22 @@ impl Collisionable for Ennemy {
     }
 
     fn get_hitbox(&self) -> (f32, f32, f32, f32) {
-        (self.x(), self.y(), ENNEMY_HITBOX_WIDTH, ENNEMY_HITBOX_HEIGHT)
+        (self.x(), self.y(), self.archetype.hitbox.0, self.archetype.hitbox.1)
+    }
+
+    fn collision_filter(&self) -> CollisionFilter {
+        CollisionFilter::ENNEMY
     }
 }
 
 impl EntityBehavior for Ennemy {
+    // Only called on the "hit" event of its attack
     fn attack(&mut self, target: &mut dyn EntityBehavior) -> bool {
-        if !self.is_taking_damage() {
-            self.state = EnnemyState::Attacking;
-        }
-        if self.attacking_frame_counter == 6 && !self.attacking_has_hit{
-            self.attacking_has_hit = true;
-            return target.get_attacked(self.attack);
-        }
-        false
+        target.get_attacked(self.archetype.attack)
     }
 
     fn get_attacked(&mut self, damage: i32) -> bool {
-        if rand::random::<f32>() > self.defense_ratio {
+        if self.rng.gen::<f32>() > self.archetype.defence_ratio {
             self.take_damage(damage);
             if self.self_entity.health() <= 0 {
                 println!("ennemy died");
@@ -354,14 +520,6 @@ impl EntityBehavior for Ennemy {
         self.self_entity.set_y(y)
     }
 
-    fn add_x(&mut self, x: f32) {
-        self.self_entity.add_x(x)
-    }
-
-    fn add_y(&mut self, y: f32) {
-        self.self_entity.add_y(y)
-    }
-
     fn facing_direction(&self) -> Option<FacingDirection> {
         self.self_entity.facing_direction()
     }
@@ -373,22 +531,18 @@ impl EntityBehavior for Ennemy {
 
 fn summon_ennemy(
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>, 
-    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+    atlases: &AtlasRegistry,
+    game_rng: &Res<GameRng>,
     game_config: &Res<GameConfig>,
-    nb: i32
+    ennemy_archetypes: &Res<EnnemyArchetypes>,
+    nb: u32
 ) {
-    
-    let texture_handle = asset_server.load("Skeleton/Idle.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(150., 150.), 4, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
-
-
-
-    let mut rng = StdRng::seed_from_u64(game_config.seed + OFFSET_ENNEMY + nb as u64);
+    let mut rng = game_rng.sub_stream(OFFSET_ENNEMY, nb as u64);
+    let archetype = ennemy_archetypes.pick(&mut rng);
 
-    let max_value_x = MAP_SIZE / 2. - SANCTUARY_WIDTH / 2.;
-    let max_value_y = MAP_SIZE / 2. - SANCTUARY_HEIGHT / 2.;
+    let max_value_x = game_config.map_size / 2. - SANCTUARY_WIDTH / 2.;
+    let max_value_y = game_config.map_size / 2. - SANCTUARY_HEIGHT / 2.;
+    let aggro_distance = archetype.aggro_distance;
 
     let mut x: f32;
     let mut y: f32;
@@ -396,42 +550,52 @@ fn summon_ennemy(
     loop {
         x = rng.gen_range(-max_value_x..max_value_x);
         y = rng.gen_range(-max_value_y..max_value_y);
-        if (x< -ENNEMY_AGGRO_DISTANCE || x > ENNEMY_AGGRO_DISTANCE) && (y < -ENNEMY_AGGRO_DISTANCE || y > ENNEMY_AGGRO_DISTANCE) {
+        if (x< -aggro_distance || x > aggro_distance) && (y < -aggro_distance || y > aggro_distance) {
             break;
         }
     }
 
-    let ennemy: Ennemy = Ennemy::new(x, y, 10, 5, ENNEMY_DEFENCE_RATIO);
-    let hitbox = CollisionComponent::new(ennemy.x(), ennemy.y(), ENNEMY_HITBOX_WIDTH, ENNEMY_HITBOX_HEIGHT);
-    let entity = (SpriteSheetBundle {
-        texture_atlas: texture_atlas_handle.clone(),
-        transform: Transform {
-            translation: Vec3::new(ennemy.x() as f32, ennemy.y() as f32, Z_LAYER_ENNEMIES),
-            scale: Vec3::new(ENNEMY_SPRITE_SCALE, ENNEMY_SPRITE_SCALE, 1.),
-            ..Default::default()
-        },
-        sprite: TextureAtlasSprite::new(0),
+    let ennemy: Ennemy = Ennemy::new(nb, x, y, archetype, game_rng.sub_stream(OFFSET_ENNEMY_AI, nb as u64));
+    spawn_ennemy(commands, atlases, ennemy);
+}
+
+pub fn spawn_ennemy(
+    commands: &mut Commands,
+    atlases: &AtlasRegistry,
+    ennemy: Ennemy,
+) {
+    let archetype = &ennemy.archetype;
+    let hitbox = CollisionComponent::new(ennemy.x(), ennemy.y(), archetype.hitbox.0, archetype.hitbox.1, CollisionFilter::ENNEMY);
+    let (r, g, b) = archetype.tint;
+    let animator = Animator::new(ennemy.state);
+    let sprite = TextureAtlasSprite { color: Color::rgb(r, g, b), ..TextureAtlasSprite::new(animator.atlas_index(&archetype.animations)) };
+    spawn_sprite_sheet(commands, &atlases.atlas(animator.sheet(&archetype.animations)), sprite, Transform {
+        translation: Vec3::new(ennemy.x(), ennemy.y(), Z_LAYER_ENNEMIES),
+        scale: Vec3::new(archetype.sprite_scale, archetype.sprite_scale, 1.),
         ..Default::default()
-    }, ennemy, hitbox);
-    commands.spawn(entity);
+    }).insert((PreviousPosition::new(ennemy.x(), ennemy.y()), animator, HitReaction::new(archetype.invulnerability), ennemy, hitbox));
 }
 
 fn summon_ennemies(
     mut commands: Commands,
-    asset_server: Res<AssetServer>, 
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    atlases: Res<AtlasRegistry>,
+    game_rng: Res<GameRng>,
     game_config: Res<GameConfig>,
+    ennemy_archetypes: Res<EnnemyArchetypes>,
 ) {
-    for i in 0..ENNEMIES_NUMBER {
-        summon_ennemy(&mut commands, &asset_server, &mut texture_atlases, &game_config, i);
+    for i in 0..game_config.ennemies_number {
+        summon_ennemy(&mut commands, &atlases, &game_rng, &game_config, &ennemy_archetypes, i);
     }
 }
 
 fn update_ennemy_position(
-    mut query: Query<(&mut Transform, &Ennemy)>,
+    mut query: Query<(&mut Transform, &PreviousPosition, &Ennemy)>,
+    fixed_time: Res<FixedTime>,
 ) {
-    for (mut transform, ennemy) in query.iter_mut() {
-        transform.translation = Vec3::new(ennemy.x() as f32, ennemy.y() as f32, Z_LAYER_ENNEMIES);
+    let alpha = interpolation_alpha(&fixed_time);
+    for (mut transform, previous, ennemy) in query.iter_mut() {
+        let (x, y) = previous.lerp(ennemy.x(), ennemy.y(), alpha);
+        transform.translation = Vec3::new(x, y, Z_LAYER_ENNEMIES);
     }
 }
 
@@ -441,57 +605,40 @@ fn update_ennemy_hitbox(
     update_collisionable_pos::<Ennemy>(&mut query);
 }
 
+// A melee ennemy starts its attack when the player is within reach in front of it, the blow lands
+// on the "hit" event of the attack if the player is still there and not recovering from another one
 fn ennemy_attack(
-    mut ennemy_query: Query<&mut Ennemy>,
-    mut player_query: Query<&mut Player>,
+    mut ennemy_query: Query<(Entity, &mut Ennemy)>,
+    mut player_query: Query<(&mut Player, &mut HitReaction)>,
+    mut animation_events: EventReader<AnimationEvent>,
+    mut shake: EventWriter<CameraShake>,
 ) {
-    let mut player = player_query.single_mut();
-    for mut ennemy in ennemy_query.iter_mut() {
-        if !ennemy.is_blocking() && !ennemy.is_dying() && !ennemy.is_dead() && !ennemy.is_loading() {
+    let hits: HashSet<Entity> = animation_events.iter().filter(|event| event.name == "hit").map(|event| event.entity).collect();
+    let (mut player, mut reaction) = player_query.single_mut();
+    for (entity, mut ennemy) in ennemy_query.iter_mut() {
+        if !ennemy.is_ranged() && !ennemy.is_blocking() && !ennemy.is_dying() && !ennemy.is_dead() && !ennemy.is_loading() {
             if let Some(direction) = ennemy.facing_direction() {
                 let actual_player: &mut Player = &mut player;
                 if actual_player.is_aggroable() {
-                match direction {
-                    FacingDirection::Up => {
-                        if ennemy.would_collide(ennemy.x(), ennemy.y() + ENNEMY_ATTACK_RANGE, &actual_player.get_collision_component()) && collisions::equals(&direction, actual_player.get_relative_position(&ennemy.get_collision_component())) {
-                            ennemy.attack(actual_player);
-                        }
-                    },
-                    FacingDirection::Down => {
-                        if ennemy.would_collide(ennemy.x(), ennemy.y() - ENNEMY_ATTACK_RANGE, &actual_player.get_collision_component()) && collisions::equals(&direction, actual_player.get_relative_position(&ennemy.get_collision_component())) {
-                            ennemy.attack(actual_player);
-                        }
-                    },
-                    FacingDirection::Left => {
-                        if ennemy.would_collide(ennemy.x() - ENNEMY_ATTACK_RANGE, ennemy.y(), &actual_player.get_collision_component()) && collisions::equals(&direction, actual_player.get_relative_position(&ennemy.get_collision_component())) {
-                            ennemy.attack(actual_player);
-                        }
-                    },
-                    FacingDirection::Right => {
-                        if ennemy.would_collide(ennemy.x() + ENNEMY_ATTACK_RANGE, ennemy.y(), &actual_player.get_collision_component()) && collisions::equals(&direction, actual_player.get_relative_position(&ennemy.get_collision_component())) {
-                            ennemy.attack(actual_player);
-                        }
-                    },
-                    FacingDirection::TopLeft => {
-                        if ennemy.would_collide(ennemy.x() - ENNEMY_ATTACK_RANGE, ennemy.y() + ENNEMY_ATTACK_RANGE, &actual_player.get_collision_component()) && collisions::equals(&direction, actual_player.get_relative_position(&ennemy.get_collision_component())) {
-                            ennemy.attack(actual_player);
-                        }
-                    },
-                    FacingDirection::TopRight => {
-                        if ennemy.would_collide(ennemy.x() + ENNEMY_ATTACK_RANGE, ennemy.y() + ENNEMY_ATTACK_RANGE, &actual_player.get_collision_component()) && collisions::equals(&direction, actual_player.get_relative_position(&ennemy.get_collision_component())) {
-                            ennemy.attack(actual_player);
-                        }
-                    },
-                    FacingDirection::BottomLeft => {
-                        if ennemy.would_collide(ennemy.x() - ENNEMY_ATTACK_RANGE, ennemy.y() - ENNEMY_ATTACK_RANGE, &actual_player.get_collision_component()) && collisions::equals(&direction, actual_player.get_relative_position(&ennemy.get_collision_component())) {
-                            ennemy.attack(actual_player);
-                        }
-                    },
-                    FacingDirection::BottomRight => {
-                        if ennemy.would_collide(ennemy.x() + ENNEMY_ATTACK_RANGE, ennemy.y() - ENNEMY_ATTACK_RANGE, &actual_player.get_collision_component()) && collisions::equals(&direction, actual_player.get_relative_position(&ennemy.get_collision_component())) {
-                            ennemy.attack(actual_player);
-                        }
-                    },
+                let (dx, dy) = match direction {
+                    FacingDirection::Up => (0., ENNEMY_ATTACK_RANGE),
+                    FacingDirection::Down => (0., -ENNEMY_ATTACK_RANGE),
+                    FacingDirection::Left => (-ENNEMY_ATTACK_RANGE, 0.),
+                    FacingDirection::Right => (ENNEMY_ATTACK_RANGE, 0.),
+                    FacingDirection::TopLeft => (-ENNEMY_ATTACK_RANGE, ENNEMY_ATTACK_RANGE),
+                    FacingDirection::TopRight => (ENNEMY_ATTACK_RANGE, ENNEMY_ATTACK_RANGE),
+                    FacingDirection::BottomLeft => (-ENNEMY_ATTACK_RANGE, -ENNEMY_ATTACK_RANGE),
+                    FacingDirection::BottomRight => (ENNEMY_ATTACK_RANGE, -ENNEMY_ATTACK_RANGE),
+                };
+                if ennemy.would_collide(ennemy.x() + dx, ennemy.y() + dy, &actual_player.get_collision_component())
+                    && collisions::equals(&direction, actual_player.get_relative_position(&ennemy.get_collision_component())) {
+                    if ennemy.is_attacking() && hits.contains(&entity) && !reaction.is_invulnerable() && ennemy.attack(actual_player) {
+                        let direction = Vec2::new(actual_player.x() - ennemy.x(), actual_player.y() - ennemy.y());
+                        reaction.hit(direction, ennemy.archetype.knockback);
+                        shake.send(CameraShake { trauma: CAMERA_DAMAGE_TRAUMA });
+                    } else if !ennemy.is_taking_damage() {
+                        ennemy.state = EnnemyState::Attacking;
+                    }
                 }
             }}
             
@@ -499,6 +646,46 @@ fn ennemy_attack(
     }
 }
 
+// A ranged ennemy in sight of the player starts its attack once reloaded, the projectile leaves on
+// the "hit" event of the attack
+fn ennemy_shoot(
+    mut commands: Commands,
+    mut ennemy_query: Query<(Entity, &mut Ennemy)>,
+    player_query: Query<&Player>,
+    mut animation_events: EventReader<AnimationEvent>,
+    surroundings: Surroundings,
+    fixed_time: Res<FixedTime>,
+    headless: Option<Res<Headless>>,
+) {
+    let hits: HashSet<Entity> = animation_events.iter().filter(|event| event.name == "hit").map(|event| event.entity).collect();
+    let player = player_query.single();
+    let (player_x, player_y) = player.get_pos();
+    let player_position = Vec2::new(player_x, player_y);
+    let delta = fixed_time.period.as_secs_f32();
+    for (entity, mut ennemy) in ennemy_query.iter_mut() {
+        let Some(ranged) = ennemy.archetype.ranged.clone() else {
+            continue;
+        };
+        ennemy.reload_time_left = (ennemy.reload_time_left - delta).max(0.);
+        let position = Vec2::new(ennemy.x(), ennemy.y());
+        let to_player = player_position - position;
+
+        if ennemy.state == EnnemyState::Chasing && ennemy.reload_time_left <= 0. && player.is_aggroable()
+            && to_player.length() <= ranged.range && ennemy.can_see(entity, player_position, &surroundings) {
+            ennemy.state = EnnemyState::Attacking;
+        }
+
+        if ennemy.is_attacking() && hits.contains(&entity) {
+            ennemy.reload_time_left = ranged.reload_time;
+            // Visé là où se tient le joueur au moment du tir
+            let direction = to_player.try_normalize().unwrap_or(ennemy.facing_direction().unwrap_or(FacingDirection::Down).to_vec2());
+            let projectile = Projectile::new(entity, position, direction * ranged.projectile_speed,
+                ranged.range / ranged.projectile_speed, ennemy.archetype.attack, ennemy.archetype.knockback, CollisionFilter::PROJECTILE.ignoring(Layers::ENNEMY));
+            spawn_projectile(&mut commands, headless.is_none(), projectile);
+        }
+    }
+}
+
 fn despawn_on_death(
     mut commands: Commands,
     mut query: Query<(Entity, &Ennemy)>,
@@ -511,192 +698,142 @@ fn despawn_on_death(
 }
 
 fn ennemy_aggro_detection(
-    mut ennemy_query: Query<(&mut Ennemy, &Transform)>,
-    player_query: Query<(&Player, &Transform)>,
-    collision_query: Query<&CollisionComponent, Without<Ennemy>>
+    mut ennemy_query: Query<(Entity, &mut Ennemy)>,
+    player_query: Query<&Player>,
+    surroundings: Surroundings,
+    fixed_time: Res<FixedTime>,
+    game_config: Res<GameConfig>,
 ) {
-    let (player, player_transform) = player_query.single();
-    for (mut ennemy, transform) in ennemy_query.iter_mut() {
-        let distance = transform.translation.distance(player_transform.translation);
+    let player = player_query.single();
+    let delta = fixed_time.period.as_secs_f32();
+    let (player_x, player_y) = player.get_pos();
+    let player_position = Vec2::new(player_x, player_y);
+    for (entity, mut ennemy) in ennemy_query.iter_mut() {
+        let distance = Vec2::new(ennemy.x(), ennemy.y()).distance(player_position);
         if !ennemy.is_doing_something() {
-            if distance < ENNEMY_AGGRO_DISTANCE && player.is_aggroable() {
-                ennemy.chase_player(&player, &collision_query);
-            
+            let seen = player.is_aggroable() && ennemy.can_see(entity, player_position, &surroundings);
+            let heard = player.is_aggroable() && distance < player.noise_radius();
+            if seen {
+                ennemy.chase_player(entity, &player, &surroundings, delta, game_config.map_size);
+            } else if heard {
+                ennemy.investigate(entity, player_position, &surroundings, delta, game_config.map_size);
+            } else if ennemy.last_seen.is_some() {
+                ennemy.search(entity, &surroundings, delta, game_config.map_size);
             } else {
-                ennemy.roaming(&collision_query);
+                ennemy.roaming(entity, &surroundings, delta, game_config.map_size);
             }
         }    
     }
 }
 
+// A few paths per tick, to the chasing ennemies that have been waiting the longest
+fn plan_ennemy_paths(
+    mut ennemy_query: Query<&mut Ennemy>,
+    player_query: Query<&Player>,
+    nav_grid: Res<NavGrid>,
+) {
+    let (player_x, player_y) = player_query.single().get_pos();
+    let player_position = Vec2::new(player_x, player_y);
+
+    let mut waiting: Vec<(Mut<Ennemy>, Vec2)> = ennemy_query.iter_mut()
+        .filter_map(|ennemy| {
+            let destination = ennemy.destination(player_position)?;
+            ennemy.needs_path(destination).then_some((ennemy, destination))
+        })
+        .collect();
+    for (ennemy, _) in waiting.iter_mut() {
+        ennemy.ticks_waiting_for_path += 1;
+    }
+    waiting.sort_by_key(|(ennemy, _)| (Reverse(ennemy.ticks_waiting_for_path), ennemy.id));
+
+    for (mut ennemy, destination) in waiting.into_iter().take(NAV_PATHS_PER_TICK) {
+        // No way to get any closer, it keeps heading straight there until the destination moves
+        let path = nav_grid.find_path(Vec2::new(ennemy.x(), ennemy.y()), destination).unwrap_or_default();
+        ennemy.set_path(path, destination);
+    }
+}
+
+// Ennemies move one after the other against where the others stood at the start of the tick,
+// the ones that end up overlapping push each other apart, half the way each
+fn separate_ennemies(
+    mut ennemy_query: Query<(Entity, &mut Ennemy)>,
+    surroundings: Surroundings,
+    game_config: Res<GameConfig>,
+) {
+    let mut ennemies_index = SpatialIndex::default();
+    let mut colliders = HashMap::new();
+    let mut order = Vec::new();
+    for (entity, ennemy) in ennemy_query.iter() {
+        ennemies_index.insert(entity, &ennemy.get_collision_component());
+        colliders.insert(entity, ennemy.collider());
+        order.push(entity);
+    }
+
+    let mut pushes: HashMap<Entity, Vec2> = HashMap::new();
+    for entity in order {
+        let Collider { center, shape } = colliders[&entity];
+        let size = shape.size();
+        // Chaque paire une seule fois, depuis sa première entité
+        for other in ennemies_index.colliders_near(center.x, center.y, size.x, size.y).into_iter().filter(|other| *other > entity) {
+            if let Some(push) = penetration(colliders[&entity], colliders[&other]) {
+                *pushes.entry(entity).or_default() += push / 2.;
+                *pushes.entry(other).or_default() -= push / 2.;
+            }
+        }
+    }
+
+    for (entity, mut ennemy) in ennemy_query.iter_mut() {
+        if let Some(push) = pushes.get(&entity) {
+            // The other ennemies are already taken care of by their own push
+            let filter = ennemy.collision_filter().ignoring(Layers::ENNEMY);
+            ennemy.move_by(entity, *push, filter, &surroundings, game_config.map_size);
+        }
+    }
+}
+
 fn state_speed_update(
-    mut ennemy_query: Query<&mut Ennemy>
+    mut ennemy_query: Query<&mut Ennemy>,
 )
  {
     for mut ennemy in ennemy_query.iter_mut() {
         match ennemy.state {
-            EnnemyState::Roaming => ennemy.current_speed = ENNEMY_NORMAL_SPEED,
-            EnnemyState::Chasing => ennemy.current_speed = ENNEMY_SPRINT_SPEED,
+            EnnemyState::Roaming | EnnemyState::Searching => ennemy.current_speed = ennemy.archetype.normal_speed,
+            EnnemyState::Chasing => ennemy.current_speed = ennemy.archetype.sprint_speed,
             _ => (),
         }
     }
  }
 
- fn update_ennemy_sprite(
-    mut query: Query<(&mut Ennemy, &mut TextureAtlasSprite, &mut Handle<TextureAtlas>)>,
-    asset_server: Res<AssetServer>, 
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    time: Res<Time>,
+// Clips that are over hand the ennemy over to their next state
+fn update_ennemy_animation(
+    mut query: Query<(Entity, &mut Ennemy, &mut Animator<EnnemyState>)>,
+    mut animation_events: EventWriter<AnimationEvent>,
+    fixed_time: Res<FixedTime>,
 ) {
-    for (mut ennemy, mut sprite, mut texture) in query.iter_mut() {
-        match ennemy.state {
-            EnnemyState::Idle => {
-                let texture_handle = asset_server.load("Skeleton/Idle.png");
-                let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(150., 150.), 4, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-                let texture_atlas_handle = texture_atlases.add(texture_atlas);
-
-                *texture = texture_atlas_handle.clone();
-                
-                ennemy.roaming_frame_time += time.delta_seconds();
-                if ennemy.roaming_frame_time >= 0.3 {
-                    ennemy.roaming_frame_counter += 1;
-                    ennemy.roaming_frame_time = 0.;
-                }
-
-                if ennemy.roaming_frame_counter >= 4 {
-                    ennemy.roaming_frame_counter = 0;
-                }
-
-                sprite.index = ennemy.roaming_frame_counter;
-            },
-            EnnemyState::Roaming => {
-                
-                let texture_handle = asset_server.load("Skeleton/Walk.png");
-                let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(150., 150.), 4, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-                let texture_atlas_handle = texture_atlases.add(texture_atlas);
-
-                *texture = texture_atlas_handle.clone();
-                
-                ennemy.roaming_frame_time += time.delta_seconds();
-                if ennemy.roaming_frame_time >= 0.3 {
-                    ennemy.roaming_frame_counter += 1;
-                    ennemy.roaming_frame_time = 0.;
-                }
-
-                if ennemy.roaming_frame_counter >= 4 {
-                    ennemy.roaming_frame_counter = 0;
-                }
-
-                sprite.index = ennemy.roaming_frame_counter;
-            },
-            EnnemyState::Chasing => {
-                    
-                    let texture_handle = asset_server.load("Skeleton/Walk.png");
-                    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(150., 150.), 4, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-                    let texture_atlas_handle = texture_atlases.add(texture_atlas);
-    
-                    *texture = texture_atlas_handle.clone();
-                    
-                    ennemy.chasing_frame_time += time.delta_seconds();
-                    if ennemy.chasing_frame_time >= 0.1 {
-                        ennemy.chasing_frame_counter += 1;
-                        ennemy.chasing_frame_time = 0.;
-                    }
-    
-                    if ennemy.chasing_frame_counter >= 4 {
-                        ennemy.chasing_frame_counter = 0;
-                    }
-    
-                    sprite.index = ennemy.chasing_frame_counter;
-            },
-            EnnemyState::Damaged => {
-                let texture_handle = asset_server.load("Skeleton/Take Hit.png");
-                let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(150., 150.), 4, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-                let texture_atlas_handle = texture_atlases.add(texture_atlas);
-
-                *texture = texture_atlas_handle.clone();
-                
-                ennemy.damaged_frame_time += time.delta_seconds();
-                if ennemy.damaged_frame_time >= 0.2 {
-                    ennemy.damaged_frame_counter += 1;
-                    ennemy.damaged_frame_time = 0.;
-                }
-
-                if ennemy.damaged_frame_counter >= 4 {
-                    ennemy.damaged_frame_counter = 0;
-                    ennemy.state = EnnemyState::Chasing;
-                }   
-
-                sprite.index = ennemy.damaged_frame_counter;
-            },
-            EnnemyState::Attacking => {
-                let texture_handle = asset_server.load("Skeleton/Attack.png");
-                let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(150., 150.), 8, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-                let texture_atlas_handle = texture_atlases.add(texture_atlas);
-
-                *texture = texture_atlas_handle.clone();
-                
-                ennemy.attacking_frame_time += time.delta_seconds();
-                if ennemy.attacking_frame_time >= ENNEMY_ATTACK_SPEED/8. {
-                    ennemy.attacking_frame_counter += 1;
-                    ennemy.attacking_frame_time = 0.;
-                }
-
-                if ennemy.attacking_frame_counter >= 8 {
-                    ennemy.attacking_frame_counter = 0;
-                    ennemy.attacking_has_hit = false;
-                    ennemy.state = EnnemyState::Chasing;
-                }   
-
-                sprite.index = ennemy.attacking_frame_counter;
-            },
-            EnnemyState::Blocking => {
-                let texture_handle = asset_server.load("Skeleton/Shield.png");
-                let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(150., 150.), 4, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-                let texture_atlas_handle = texture_atlases.add(texture_atlas);
-
-                *texture = texture_atlas_handle.clone();
-                
-                ennemy.blocking_frame_time += time.delta_seconds();
-                if ennemy.blocking_frame_time >= 0.1 {
-                    ennemy.blocking_frame_counter += 1;
-                    ennemy.blocking_frame_time = 0.;
-                }
-
-                if ennemy.blocking_frame_counter >= 4 {
-                    ennemy.blocking_frame_counter = 0;
-                    ennemy.state = EnnemyState::Chasing;
-                }   
-
-                sprite.index = ennemy.blocking_frame_counter;
-            },
-            EnnemyState::Dying => {
-                let texture_handle = asset_server.load("Skeleton/Death.png");
-                let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(150., 150.), 4, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-                let texture_atlas_handle = texture_atlases.add(texture_atlas);
-
-                *texture = texture_atlas_handle.clone();
-                
-                ennemy.dying_frame_time += time.delta_seconds();
-                if ennemy.dying_frame_time >= 0.2 {
-                    ennemy.dying_frame_counter += 1;
-                    ennemy.dying_frame_time = 0.;
-                }
-
-                if ennemy.dying_frame_counter >= 4 {
-                    ennemy.dying_frame_counter = 0;
-                    ennemy.state = EnnemyState::Dead;
-                }   
+    let delta = fixed_time.period.as_secs_f32();
+    for (entity, mut ennemy, mut animator) in query.iter_mut() {
+        animator.play(ennemy.state);
+        let next = animator.advance(&ennemy.archetype.animations, delta, |name| {
+            animation_events.send(AnimationEvent { entity, name: name.to_string() });
+        });
+        if let Some(next) = next {
+            ennemy.state = next;
+            animator.play(next);
+        }
+    }
+}
 
-                sprite.index = ennemy.dying_frame_counter;
-            },
-            EnnemyState::Dead => {
-                sprite.index = 3;
-            },
-            EnnemyState::Loading => {
-                sprite.index = 0;
-            },
+// Only swaps the atlas when the clip comes from another sheet
+fn update_ennemy_sprite(
+    mut query: Query<(&Ennemy, &Animator<EnnemyState>, &mut TextureAtlasSprite, &mut Handle<TextureAtlas>)>,
+    atlases: Res<AtlasRegistry>,
+) {
+    for (ennemy, animator, mut sprite, mut texture) in query.iter_mut() {
+        let animations = &ennemy.archetype.animations;
+        if let Some(handle) = atlases.atlas(animator.sheet(animations)).filter(|handle| *handle != *texture) {
+            *texture = handle;
         }
+        sprite.index = animator.atlas_index(animations);
     }
 }
 
@@ -707,22 +844,22 @@ fn change_sprite_orientation(
         if let Some(direction) = ennemy.facing_direction() {
             match direction {
                 FacingDirection::Left => {
-                    transform.scale.x = -ENNEMY_SPRITE_SCALE;
+                    transform.scale.x = -ennemy.archetype.sprite_scale;
                 },
                 FacingDirection::Right => {
-                    transform.scale.x = ENNEMY_SPRITE_SCALE;
+                    transform.scale.x = ennemy.archetype.sprite_scale;
                 },
                 FacingDirection::TopLeft => {
-                    transform.scale.x = -ENNEMY_SPRITE_SCALE;
+                    transform.scale.x = -ennemy.archetype.sprite_scale;
                 },
                 FacingDirection::TopRight => {
-                    transform.scale.x = ENNEMY_SPRITE_SCALE;
+                    transform.scale.x = ennemy.archetype.sprite_scale;
                 },
                 FacingDirection::BottomLeft => {
-                    transform.scale.x = -ENNEMY_SPRITE_SCALE;
+                    transform.scale.x = -ennemy.archetype.sprite_scale;
                 },
                 FacingDirection::BottomRight => {
-                    transform.scale.x = ENNEMY_SPRITE_SCALE;
+                    transform.scale.x = ennemy.archetype.sprite_scale;
                 },
                 _ => (),
             }
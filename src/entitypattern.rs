@@ -1,4 +1,7 @@
-#[derive(PartialEq, Clone, Copy)]
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum FacingDirection {
     Up,
     Down,
@@ -10,6 +13,54 @@ pub enum FacingDirection {
     BottomRight,
 }
 
+impl FacingDirection {
+    // Unit vector pointing where the entity looks
+    pub fn to_vec2(self) -> Vec2 {
+        match self {
+            FacingDirection::Up => Vec2::Y,
+            FacingDirection::Down => Vec2::NEG_Y,
+            FacingDirection::Left => Vec2::NEG_X,
+            FacingDirection::Right => Vec2::X,
+            FacingDirection::TopLeft => Vec2::new(-1., 1.).normalize(),
+            FacingDirection::TopRight => Vec2::new(1., 1.).normalize(),
+            FacingDirection::BottomLeft => Vec2::new(-1., -1.).normalize(),
+            FacingDirection::BottomRight => Vec2::new(1., -1.).normalize(),
+        }
+    }
+
+    // The one of the eight directions closest to a vector, none for a zero vector
+    pub fn from_vec2(vector: Vec2) -> Option<FacingDirection> {
+        if vector == Vec2::ZERO {
+            return None;
+        }
+        let octant = (vector.y.atan2(vector.x) / std::f32::consts::FRAC_PI_4).round() as i32;
+        Some(match octant.rem_euclid(8) {
+            0 => FacingDirection::Right,
+            1 => FacingDirection::TopRight,
+            2 => FacingDirection::Up,
+            3 => FacingDirection::TopLeft,
+            4 => FacingDirection::Left,
+            5 => FacingDirection::BottomLeft,
+            6 => FacingDirection::Down,
+            _ => FacingDirection::BottomRight,
+        })
+    }
+
+    // Next of the eight directions, turning clockwise
+    pub fn clockwise(self) -> FacingDirection {
+        match self {
+            FacingDirection::Up => FacingDirection::TopRight,
+            FacingDirection::TopRight => FacingDirection::Right,
+            FacingDirection::Right => FacingDirection::BottomRight,
+            FacingDirection::BottomRight => FacingDirection::Down,
+            FacingDirection::Down => FacingDirection::BottomLeft,
+            FacingDirection::BottomLeft => FacingDirection::Left,
+            FacingDirection::Left => FacingDirection::TopLeft,
+            FacingDirection::TopLeft => FacingDirection::Up,
+        }
+    }
+}
+
 pub struct EntityPatern {
     x: f32,
     y: f32,
@@ -27,8 +78,6 @@ pub trait EntityBehavior {
     fn y(&self) -> f32;
     fn set_x(&mut self, x: f32);
     fn set_y(&mut self, y: f32);
-    fn add_x(&mut self, x: f32);
-    fn add_y(&mut self, y: f32);
 
     fn facing_direction(&self) -> Option<FacingDirection>;
     fn set_facing_direction(&mut self, facing_direction: FacingDirection);
@@ -71,14 +120,6 @@ impl EntityPatern {
         self.facing_direction = Some(facing_direction);
     }
 
-    pub fn add_x(&mut self, x: f32) {
-        self.x += x
-    }
-
-    pub fn add_y(&mut self, y: f32) {
-        self.y += y
-    }
-
     pub fn health(&self) -> i32 {
         self.health
     }
@@ -87,4 +128,43 @@ impl EntityPatern {
         self.health += health;
     }
 
+    pub fn set_health(&mut self, health: i32) {
+        self.health = health;
+    }
+
+    pub fn set_facing(&mut self, facing_direction: Option<FacingDirection>) {
+        self.facing_direction = facing_direction;
+    }
+
+}
+
+// Position at the start of the current tick, so sprites can be drawn between two ticks
+#[derive(Component)]
+pub struct PreviousPosition {
+    x: f32,
+    y: f32,
+}
+
+impl PreviousPosition {
+    pub fn new(x: f32, y: f32) -> Self {
+        PreviousPosition { x, y }
+    }
+
+    pub fn lerp(&self, x: f32, y: f32, alpha: f32) -> (f32, f32) {
+        (self.x + (x - self.x) * alpha, self.y + (y - self.y) * alpha)
+    }
+}
+
+pub fn save_previous_pos<T: EntityBehavior + Component>(
+    mut query: Query<(&mut PreviousPosition, &T)>,
+) {
+    for (mut previous, entity) in query.iter_mut() {
+        previous.x = entity.x();
+        previous.y = entity.y();
+    }
+}
+
+// How far the render frame is between the last tick and the next one
+pub fn interpolation_alpha(fixed_time: &FixedTime) -> f32 {
+    (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.)
 }
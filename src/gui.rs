@@ -1,15 +1,15 @@
 use bevy::prelude::*;
-use crate::{constants::*, structures::Sanctuary, collisions::{*, self}, GameState};
+use crate::{constants::*, structures::Sanctuary, collisions::{*, self}, camera::ViewArea, player::Player, GameState};
 
 pub struct GUIPlugin;
 
 impl Plugin for GUIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnExit(GameState::Menu), setup_gui)
-            .add_systems(OnExit(GameState::Loading), setup_gui)
+        app.add_systems(OnExit(GameState::Loading), (setup_gui, spawn_stamina_gauge))
               .add_systems(Update, (update_visibility, 
                                                       update_gui_pos,
-                                                      update_display_pos).distributive_run_if(in_state(GameState::Playing)));
+                                                      update_display_pos,
+                                                      update_stamina_gauge).distributive_run_if(in_state(GameState::Playing)));
     }
 }
 
@@ -44,7 +44,7 @@ fn setup_gui(mut commands: Commands,
             translation: Vec3::new(0., 0., Z_LAYER_GUI),
             ..Transform::default()
         },
-        texture: asset_server.load("UI/s.png"),
+        texture: asset_server.load(SANCTUARY_MARKER_IMAGE),
         ..Default::default()
     }, gui));
 }
@@ -52,7 +52,8 @@ fn setup_gui(mut commands: Commands,
 
 fn update_gui_pos(mut query: Query<&mut GUI>,  
                   visible_sanctuary_query: Query<&Sanctuary>, 
-                  camera_pos: Query<&Transform, With<Camera>>) 
+                  camera_pos: Query<&Transform, With<Camera>>,
+                  view_area: Res<ViewArea>) 
 {
     let mut gui = query.single_mut();
     let camera_pos = camera_pos.single();
@@ -64,28 +65,28 @@ fn update_gui_pos(mut query: Query<&mut GUI>,
     let sanct_pos = if sanct_pos.len() > 0 {sanct_pos[0].get_pos()} else {gui.visible = false; return};
 
 
-    if is_sanct_visible(sanct_pos.0, sanct_pos.1, camera_pos.0, camera_pos.1) {
+    if is_sanct_visible(sanct_pos.0, sanct_pos.1, camera_pos.0, camera_pos.1, &view_area) {
         gui.set_visible(false);
     } else {
         gui.set_visible(true);
     }
 
-    let (x, y) = get_gui_pos(sanct_pos.0, sanct_pos.1, camera_pos.0, camera_pos.1);
+    let (x, y) = get_gui_pos(sanct_pos.0, sanct_pos.1, camera_pos.0, camera_pos.1, &view_area);
 
     gui.x = x;
     gui.y = y;
 }
 
-fn is_sanct_visible(sanct_x: f32, sanct_y: f32, cam_x: f32, cam_y: f32) -> bool {
-    collisions::are_overlapping(sanct_x, sanct_y, SANCTUARY_WIDTH, SANCTUARY_HEIGHT, 
-                             cam_x, cam_y, CAMERA_DEFAULT_SCALE * WINDOW_WIDTH, CAMERA_DEFAULT_SCALE * WINDOW_HEIGHT)
+fn is_sanct_visible(sanct_x: f32, sanct_y: f32, cam_x: f32, cam_y: f32, view_area: &ViewArea) -> bool {
+    collisions::are_overlapping(Collider::rect(sanct_x, sanct_y, SANCTUARY_WIDTH, SANCTUARY_HEIGHT),
+                                Collider::rect(cam_x, cam_y, view_area.half_width * 2., view_area.half_height * 2.))
 }
 
-fn get_gui_pos(sanct_post_x: f32, sanct_post_y: f32, cam_x: f32, cam_y: f32) -> (f32, f32) {
-    let pos_x_border = CAMERA_DEFAULT_SCALE * WINDOW_WIDTH / 2. - 10.;
-    let neg_x_border = CAMERA_DEFAULT_SCALE * -WINDOW_WIDTH / 2. + 10.;
-    let pos_y_border = CAMERA_DEFAULT_SCALE * WINDOW_HEIGHT / 2. - 10.;
-    let neg_y_border = CAMERA_DEFAULT_SCALE * -WINDOW_HEIGHT / 2. + 10.;
+fn get_gui_pos(sanct_post_x: f32, sanct_post_y: f32, cam_x: f32, cam_y: f32, view_area: &ViewArea) -> (f32, f32) {
+    let pos_x_border = view_area.half_width - 10.;
+    let neg_x_border = -view_area.half_width + 10.;
+    let pos_y_border = view_area.half_height - 10.;
+    let neg_y_border = -view_area.half_height + 10.;
 
     let ux = (sanct_post_x - cam_x) as f32;
     let uy = (sanct_post_y - cam_y) as f32;
@@ -133,3 +134,44 @@ fn update_display_pos(mut query: Query<(&mut Transform, &GUI)>) {
     }
 }
 
+// Filled part of the stamina bar, in a corner of the screen
+#[derive(Component)]
+struct StaminaGauge;
+
+fn spawn_stamina_gauge(mut commands: Commands) {
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(20.0),
+            bottom: Val::Px(20.0),
+            width: Val::Px(150.0),
+            height: Val::Px(10.0),
+            ..default()
+        },
+        background_color: Color::DARK_GRAY.into(),
+        ..default()
+    })
+    .with_children(|parent| {
+        parent.spawn((NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::rgb(0.9, 0.8, 0.2).into(),
+            ..default()
+        }, StaminaGauge));
+    });
+}
+
+// Turns red while the player is exhausted
+fn update_stamina_gauge(
+    player_query: Query<&Player>,
+    mut gauge_query: Query<(&mut Style, &mut BackgroundColor), With<StaminaGauge>>,
+) {
+    let stamina = player_query.single().stamina();
+    for (mut style, mut color) in gauge_query.iter_mut() {
+        style.width = Val::Percent(stamina.ratio() * 100.0);
+        *color = if stamina.is_exhausted() { Color::rgb(0.8, 0.2, 0.2) } else { Color::rgb(0.9, 0.8, 0.2) }.into();
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use crate::constants::*;
+use crate::ennemies::Ennemy;
+use crate::player::Player;
+use crate::{GameState, TickSet};
+
+pub struct HeadlessPlugin {
+    pub ticks: u32,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Headless::new(self.ticks))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1. / TICK_RATE)))
+            .init_resource::<Input<KeyCode>>()
+            .add_systems(Startup, start_simulation)
+            .add_systems(FixedUpdate, count_ticks.in_set(TickSet::Structures).run_if(in_state(GameState::Playing)))
+            .add_systems(Last, exit_simulation);
+    }
+}
+
+#[derive(Resource)]
+pub struct Headless {
+    ticks: u32,
+    elapsed: u32,
+}
+
+impl Headless {
+    pub fn new(ticks: u32) -> Self {
+        Headless { ticks, elapsed: 0 }
+    }
+}
+
+// Run condition for every system that touches sprites, the camera or the asset server
+pub fn is_rendering(headless: Option<Res<Headless>>) -> bool {
+    headless.is_none()
+}
+
+// Spawns an entity drawn from `texture_atlas`, or with a bare transform when there is nothing to draw
+pub fn spawn_sprite_sheet<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    texture_atlas: &Option<Handle<TextureAtlas>>,
+    sprite: TextureAtlasSprite,
+    transform: Transform,
+) -> EntityCommands<'w, 's, 'a> {
+    match texture_atlas {
+        Some(texture_atlas) => commands.spawn(SpriteSheetBundle {
+            texture_atlas: texture_atlas.clone(),
+            transform,
+            sprite,
+            ..Default::default()
+        }),
+        None => commands.spawn(TransformBundle::from_transform(transform)),
+    }
+}
+
+fn start_simulation(mut state: ResMut<NextState<GameState>>) {
+    state.set(GameState::Loading);
+}
+
+fn count_ticks(mut headless: ResMut<Headless>) {
+    headless.elapsed += 1;
+}
+
+fn exit_simulation(
+    headless: Res<Headless>,
+    state: Res<State<GameState>>,
+    player_query: Query<&Player>,
+    ennemy_query: Query<&Ennemy>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if headless.elapsed >= headless.ticks || *state.get() == GameState::GameOver {
+        if let Ok(player) = player_query.get_single() {
+            println!("Simulation finished after {} ticks: player health {}, {} ennemies left",
+                headless.elapsed, player.health(), ennemy_query.iter().count());
+        }
+        exit.send(AppExit);
+    }
+}
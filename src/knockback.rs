@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use crate::collisions::*;
+use crate::config::GameConfig;
+use crate::constants::*;
+use crate::entitypattern::EntityBehavior;
+use crate::headless::is_rendering;
+use crate::GameState;
+
+pub struct KnockbackPlugin;
+
+impl Plugin for KnockbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, flicker.run_if(is_rendering).run_if(in_state(GameState::Playing)));
+    }
+}
+
+// What the last blow left behind: a push away from where it came from, and a while out of reach
+#[derive(Component)]
+pub struct HitReaction {
+    velocity: Vec2,
+    invulnerable_for: f32,
+    // Seconds out of reach after each blow
+    invulnerability: f32,
+}
+
+impl HitReaction {
+    pub fn new(invulnerability: f32) -> Self {
+        HitReaction { velocity: Vec2::ZERO, invulnerable_for: 0., invulnerability }
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable_for > 0.
+    }
+
+    // Straight down when there is no telling where the blow came from
+    pub fn hit(&mut self, direction: Vec2, strength: f32) {
+        self.push(direction, strength);
+        self.invulnerable_for = self.invulnerability;
+    }
+
+    // Same move as a knockback, without the blow
+    pub fn push(&mut self, direction: Vec2, strength: f32) {
+        self.velocity = direction.try_normalize().unwrap_or(Vec2::NEG_Y) * strength;
+    }
+}
+
+// The push slides along obstacles like any move and slows down every tick, so nobody ends up
+// inside a tree or out of the map
+pub fn apply_hit_reaction<T: EntityBehavior + Collisionable + Component>(
+    mut query: Query<(Entity, &mut T, &mut HitReaction)>,
+    surroundings: Surroundings,
+    fixed_time: Res<FixedTime>,
+    game_config: Res<GameConfig>,
+) {
+    let delta = fixed_time.period.as_secs_f32();
+    for (entity, mut target, mut reaction) in query.iter_mut() {
+        reaction.invulnerable_for = (reaction.invulnerable_for - delta).max(0.);
+        if reaction.velocity == Vec2::ZERO {
+            continue;
+        }
+
+        let step = reaction.velocity * delta;
+        let hitbox = target.get_hitbox();
+        let obstacles = surroundings.obstacles_on_the_way(entity, hitbox, target.collision_filter(), step);
+        let map_limit = Vec2::new(game_config.map_size / 2. - hitbox.2 / 2., game_config.map_size / 2. - hitbox.3 / 2.);
+        let position = move_and_slide(target.collider(), step, &obstacles).clamp(-map_limit, map_limit);
+        target.set_x(position.x);
+        target.set_y(position.y);
+
+        reaction.velocity *= (1. - KNOCKBACK_FRICTION * delta).max(0.);
+        if reaction.velocity.length() < KNOCKBACK_MIN_SPEED {
+            reaction.velocity = Vec2::ZERO;
+        }
+    }
+}
+
+// Fades in and out while invulnerable
+fn flicker(
+    mut query: Query<(&HitReaction, &mut TextureAtlasSprite)>,
+) {
+    for (reaction, mut sprite) in query.iter_mut() {
+        let faded = reaction.is_invulnerable() && (reaction.invulnerable_for / INVULNERABILITY_FLICKER_PERIOD) as u32 % 2 == 1;
+        sprite.color.set_a(if faded { INVULNERABILITY_FLICKER_ALPHA } else { 1. });
+    }
+}
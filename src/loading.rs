@@ -1,23 +1,47 @@
+use bevy::asset::LoadState;
 use bevy::prelude::*;
 
-use crate::{ennemies::Ennemy, player::Player, setup::{BackgroundObjects, Background}, structures::{Sanctuary, Tower}, gui::GUI, gameover::GameOver, GameState, LoadingState, buttons::create_button};
+use crate::{ennemies::Ennemy, player::Player, setup::{BackgroundObjects, Background}, structures::{Sanctuary, Tower}, gui::GUI, gameover::GameOver, projectiles::Projectile, spawners::Spawner, GameState, buttons::create_button};
+use crate::atlases::AtlasRegistry;
+use crate::headless::is_rendering;
 
 
 pub struct RestartButtonPlugin;
 
 impl Plugin for RestartButtonPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, interact_with_restart_button)
-            .add_systems(Update, load_game.run_if(in_state(GameState::Loading)));
+        app.add_systems(Update, interact_with_restart_button);
     }
 }
 
+// Every game starts in Loading, which only lets it play once the images of the atlas registry are there
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Loading), spawn_loading_screen.run_if(is_rendering))
+            .add_systems(Update, load_game.run_if(in_state(GameState::Loading)))
+            .add_systems(OnExit(GameState::Loading), despawn_loading_screen);
+    }
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingProgress;
+
+#[derive(Component)]
+struct LoadingError;
+
 
 fn interact_with_restart_button(
     mut self_button: Query<(&Interaction, &RestartButton)>,
     mut state: ResMut<NextState<GameState>>,
     mut commands: Commands,
     gameover_query: Query<Entity, With<GameOver>>,
+    projectile_query: Query<Entity, With<Projectile>>,
+    spawner_query: Query<Entity, With<Spawner>>,
     entity_query: Query<Entity, With<Ennemy>>,
     player_query: Query<Entity, With<Player>>,
     bg_element_query: Query<Entity, With<BackgroundObjects>>,
@@ -33,6 +57,9 @@ fn interact_with_restart_button(
                 for entity in gameover_query.iter() {
                     commands.entity(entity).despawn_recursive();
                 }
+                for entity in projectile_query.iter().chain(spawner_query.iter()) {
+                    commands.entity(entity).despawn();
+                }
                 despawn_everything(&mut commands, 
                                    &entity_query, 
                                    &player_query, 
@@ -87,19 +114,131 @@ fn despawn_everything(
     }
 }
 
+fn spawn_loading_screen(mut commands: Commands) {
+    commands.spawn((NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(20.0),
+            ..default()
+        },
+        background_color: Color::BLACK.into(),
+        ..default()
+    }, LoadingScreen))
+    .with_children(|parent| {
+        parent.spawn(TextBundle::from_section("Loading", TextStyle {
+            font_size: 50.0,
+            color: Color::GRAY,
+            ..default()
+        }));
+        parent.spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(400.0),
+                height: Val::Px(20.0),
+                ..default()
+            },
+            background_color: Color::DARK_GRAY.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((NodeBundle {
+                style: Style {
+                    width: Val::Percent(0.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                background_color: Color::GRAY.into(),
+                ..default()
+            }, LoadingProgress));
+        });
+    });
+}
+
+fn despawn_loading_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<LoadingScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Playing once every image is loaded. A file that fails stops the game there, on a screen that names it
 fn load_game(
+    mut commands: Commands,
     mut state: ResMut<NextState<GameState>>,
-    mut loading_state: ResMut<LoadingState>,
-    time: Res<Time>,
+    asset_server: Option<Res<AssetServer>>,
+    atlases: Res<AtlasRegistry>,
+    mut progress_query: Query<&mut Style, With<LoadingProgress>>,
+    screen_query: Query<Entity, With<LoadingScreen>>,
+    error_query: Query<(), With<LoadingError>>,
 ) {
-    loading_state.timer.tick(time.delta());
+    // Nothing to wait for without a renderer
+    let Some(asset_server) = asset_server else {
+        state.set(GameState::Playing);
+        return;
+    };
+    if !error_query.is_empty() {
+        return;
+    }
+
+    let mut loaded = 0;
+    let mut total = 0;
+    for (path, handle) in atlases.images() {
+        total += 1;
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => {
+                println!("Cannot load assets/{}", path);
+                for entity in screen_query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                spawn_error_screen(&mut commands, path);
+                return;
+            }
+            _ => {}
+        }
+    }
 
-    if loading_state.timer.finished() {
+    for mut style in progress_query.iter_mut() {
+        style.width = Val::Percent(if total == 0 { 100.0 } else { loaded as f32 * 100.0 / total as f32 });
+    }
+    if loaded == total {
         state.set(GameState::Playing);
-        loading_state.timer.reset();
     }
 }
 
+fn spawn_error_screen(commands: &mut Commands, path: &str) {
+    commands.spawn((NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(20.0),
+            ..default()
+        },
+        background_color: Color::BLACK.into(),
+        ..default()
+    }, LoadingScreen, LoadingError))
+    .with_children(|parent| {
+        parent.spawn(TextBundle::from_section("Cannot start the game", TextStyle {
+            font_size: 50.0,
+            color: Color::RED,
+            ..default()
+        }));
+        parent.spawn(TextBundle::from_section(format!("assets/{} is missing or is not a valid image", path), TextStyle {
+            font_size: 30.0,
+            color: Color::GRAY,
+            ..default()
+        }));
+    });
+}
+
 #[derive(Component)]
 pub struct RestartButton;
 
@@ -1,4 +1,8 @@
 mod player;
+mod archetypes;
+mod camera;
+mod cli;
+mod config;
 mod constants;
 mod structures;
 mod setup;
@@ -11,14 +15,47 @@ mod pause;
 mod gameover;
 mod buttons;
 mod loading;
+mod headless;
+mod rng;
+mod controls;
+mod replay;
+mod save;
+mod spatial;
+mod triggers;
+mod navigation;
+mod projectiles;
+mod spawners;
+mod animation;
+mod atlases;
+mod knockback;
 
+use std::path::PathBuf;
+use std::time::Duration;
+
+use animation::AnimationPlugin;
+use atlases::AtlasPlugin;
 use bevy::prelude::*;
 use bevy::window::WindowMode;
+use camera::{CameraPlugin, CameraShake};
+use clap::Parser;
+use cli::Cli;
+use config::GameConfig;
+use controls::ControlsPlugin;
 use ennemies::EnnemyPlugin;
 use gameover::GameOverPlugin;
-use loading::RestartButtonPlugin;
+use headless::HeadlessPlugin;
+use knockback::KnockbackPlugin;
+use loading::{LoadingPlugin, RestartButtonPlugin};
 use pause::PausePlugin;
+use replay::{Replay, ReplayMode, ReplayPlugin};
+use rng::RngPlugin;
+use save::SavePlugin;
 use setup::SetupPlugin;
+use spatial::SpatialIndexPlugin;
+use triggers::TriggerPlugin;
+use navigation::NavigationPlugin;
+use projectiles::ProjectilePlugin;
+use spawners::SpawnerPlugin;
 use crate::player::*;
 use crate::structures::*;
 use crate::gui::*;
@@ -34,45 +71,101 @@ pub enum GameState {
     Loading,
 }
 
-#[derive(Resource, Default)]
-struct LoadingState {
-    timer: Timer,
+// Gameplay runs in FixedUpdate, one chained set after the other so a tick always plays out the same way
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TickSet {
+    Input,
+    Player,
+    Ennemies,
+    Projectiles,
+    Triggers,
+    Structures,
+    Record,
 }
 
-#[derive(Resource)]
-pub struct GameConfig {
-    pub seed: u64,
+// The map is generated in this order, each step keeps clear of what the previous ones placed
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SpawnSet {
+    Structures,
+    Decor,
 }
 
-impl GameConfig {
-    pub fn new(seed: u64) -> Self {
-        GameConfig { seed }
-    }
-}
 
-impl Default for GameConfig {
-    fn default() -> Self {
-        GameConfig {
-            seed: DEFAULT_SEED,
-        }
+fn main() {
+    let cli = Cli::parse();
+    let replay = cli.replay.as_deref()
+        .map(Replay::load)
+        .transpose()
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+
+    let mut game_config = cli.game_config().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    if let Some(replay) = &replay {
+        game_config = game_config.restored(replay.config());
     }
-}
-    
+    let ennemy_archetypes = cli.ennemy_archetypes(&game_config).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    App::new()
-        .insert_resource(if args.len() > 1 {
-            GameConfig::new(args[1].parse().unwrap_or(DEFAULT_SEED))
-        } else {
-            GameConfig::default()
-        })
-        .insert_resource(LoadingState {
-            timer: Timer::from_seconds(1.0, TimerMode::Once), // 1 seconde
-        })
+    let player_animations = cli.player_animations().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    atlases::check_sheet_cuts(&player_animations, &ennemy_archetypes).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+
+    let mut app = App::new();
+    app.insert_resource(game_config)
+        .insert_resource(ennemy_archetypes)
+        .insert_resource(player_animations)
+        .insert_resource(FixedTime::new(Duration::from_secs_f64(1. / TICK_RATE)))
         .add_state::<GameState>()
-        .add_plugins((
+        .add_event::<CameraShake>()
+        .configure_sets(FixedUpdate, (
+            TickSet::Input, 
+            TickSet::Player, 
+            TickSet::Ennemies, 
+            TickSet::Projectiles,
+            TickSet::Triggers,
+            TickSet::Structures,
+            TickSet::Record,
+            ).chain())
+        .configure_sets(OnExit(GameState::Loading), (SpawnSet::Structures, SpawnSet::Decor).chain())
+        .add_plugins((SpatialIndexPlugin, TriggerPlugin, NavigationPlugin, ProjectilePlugin, SpawnerPlugin, AnimationPlugin, AtlasPlugin, LoadingPlugin, KnockbackPlugin));
+
+    let record = cli.record.clone()
+        .or_else(|| (!cli.headless).then(|| PathBuf::from(REPLAY_DEFAULT_PATH)));
+
+    if let Some(replay) = replay {
+        app.add_plugins(ReplayPlugin { mode: ReplayMode::Play(replay) });
+    } else if let Some(path) = record {
+        app.add_plugins(ReplayPlugin { mode: ReplayMode::Record(path) });
+    }
+
+    if cli.headless {
+        app.add_plugins((
+            MinimalPlugins,
+            HeadlessPlugin { ticks: cli.ticks },
+            RngPlugin,
+            ControlsPlugin,
+            PlayerPlugin,
+            SetupPlugin,
+            EnnemyPlugin,
+            StructuresPlugin));
+    } else {
+        app.add_plugins((
             DefaultPlugins, 
+            CameraPlugin,
+            RngPlugin,
+            ControlsPlugin,
             menu::MenuPlugin, 
             PlayerPlugin, 
             SetupPlugin, 
@@ -82,26 +175,22 @@ fn main() {
             PausePlugin, 
             GameOverPlugin, 
             buttons::ButtonPlugin,
-            RestartButtonPlugin))
-        .add_systems(Startup, setup_window)
-        .run();
+            RestartButtonPlugin,
+            SavePlugin))
+            .add_systems(Startup, setup_window);
+        if cli.skip_menu {
+            app.add_systems(Startup, menu::skip_menu);
+        }
+    }
+    app.run();
 }
 
 fn setup_window(
-    mut commands: Commands,
     mut windows: Query<&mut Window>,
-
+    game_config: Res<GameConfig>,
 ) {
     let mut window = windows.single_mut();
-    window.mode = WindowMode::BorderlessFullscreen;
+    window.mode = if game_config.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed };
+    window.resolution.set(game_config.resolution.0, game_config.resolution.1);
     window.title = "Zelda".to_string();
-
-    commands.spawn(Camera2dBundle {
-        projection: OrthographicProjection {
-            scale : CAMERA_DEFAULT_SCALE,
-            far: Z_LAYER_GUI,
-            ..OrthographicProjection::default()
-        },
-        ..Default::default()
-    });
 }
\ No newline at end of file
@@ -1,17 +1,23 @@
+use std::path::Path;
+
 use bevy::app::AppExit;
 use bevy::prelude::*;
 use rand::Rng;
-use crate::GameConfig;
+use crate::archetypes::EnnemyArchetypes;
+use crate::config::GameConfig;
 use crate::buttons::*;
 use crate::constants::*;
 use crate::GameState;
+use crate::rng::GameRng;
+use crate::save::{PendingLoad, SaveGame};
 
 pub struct MenuPlugin;
 
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup)
-            .add_systems(Update, (intteract_with_play_button, intteract_with_quit_button, start_on_press_space, start_random_seed).run_if(in_state(GameState::Menu)));
+            .add_systems(Update, (intteract_with_play_button, intteract_with_continue_button, intteract_with_quit_button, start_on_press_space, start_random_seed).run_if(in_state(GameState::Menu)))
+            .add_systems(OnExit(GameState::Menu), despawn_menu);
     }
 }
 
@@ -21,10 +27,12 @@ pub struct Menu;
 pub struct ButtonPlay;
 #[derive(Component)]
 pub struct ButtonQuit;
+#[derive(Component)]
+pub struct ButtonContinue;
 
 fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.spawn(SpriteBundle {
-        texture: asset_server.load("Background/background.png"),
+        texture: asset_server.load(BACKGROUND_IMAGE),
         transform: Transform {
             translation: Vec3::new(0., 0., Z_LAYER_BACKGROUND),
             ..Transform::default()
@@ -49,6 +57,11 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
             },
             ..default()
         }, Menu))
+        .with_children(|parent| {
+            if Path::new(SAVE_PATH).exists() {
+                create_button(parent, "Continue", ButtonContinue, &asset_server)
+            }
+        })
         .with_children(|parent| {
             create_button(parent, "Start Random Seed", RandomSeedButton, &asset_server)
         })
@@ -63,13 +76,43 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
 fn intteract_with_play_button(
     mut state: ResMut<NextState<GameState>>,
     mut button_query: Query<(&Interaction, &ButtonPlay)>,
-    mut commands: Commands,
-    menu_query: Query<Entity, With<Menu>>
 ) {
     for (interaction, _) in button_query.iter_mut() {
         if *interaction == Interaction::Pressed {
-            state.set(GameState::Playing);
-            despawn_menu(&mut commands, &menu_query)
+            state.set(GameState::Loading);
+        }
+    }
+}
+
+fn intteract_with_continue_button(
+    mut commands: Commands,
+    mut state: ResMut<NextState<GameState>>,
+    mut game_config: ResMut<GameConfig>,
+    mut game_rng: ResMut<GameRng>,
+    mut ennemy_archetypes: ResMut<EnnemyArchetypes>,
+    button_query: Query<&Interaction, (Changed<Interaction>, With<ButtonContinue>)>,
+) {
+    for interaction in button_query.iter() {
+        if *interaction == Interaction::Pressed {
+            match SaveGame::load(Path::new(SAVE_PATH)) {
+                Ok(save) => {
+                    // The world of the save is rebuilt from its config, ennemies scaled the way it scaled them
+                    let archetypes = match ennemy_archetypes.for_config(save.config()) {
+                        Ok(archetypes) => archetypes,
+                        Err(err) => {
+                            println!("Cannot continue: {}", err);
+                            continue;
+                        }
+                    };
+                    *game_config = game_config.restored(save.config());
+                    *ennemy_archetypes = archetypes;
+                    *game_rng = GameRng::new(game_config.seed);
+                    println!("Continuing with seed {}", game_config.seed);
+                    commands.insert_resource(PendingLoad(save));
+                    state.set(GameState::Loading);
+                }
+                Err(err) => println!("Cannot continue: {}", err),
+            }
         }
     }
 }
@@ -85,21 +128,22 @@ fn intteract_with_quit_button(
     }
 }
 
-fn despawn_menu(commands: &mut Commands, menu_query: &Query<Entity, With<Menu>>) {
+fn despawn_menu(mut commands: Commands, menu_query: Query<Entity, With<Menu>>) {
     for entity in menu_query.iter() {
         commands.entity(entity).despawn_recursive();
     }
 }
 
+pub fn skip_menu(mut state: ResMut<NextState<GameState>>) {
+    state.set(GameState::Loading);
+}
+
 fn start_on_press_space(
     mut state: ResMut<NextState<GameState>>,
     keyboard_input: Res<Input<KeyCode>>,
-    mut commands: Commands,
-    menu_query: Query<Entity, With<Menu>>
 ) {
     if keyboard_input.just_pressed(KeyCode::Space) {
-        state.set(GameState::Playing);
-        despawn_menu(&mut commands, &menu_query)
+        state.set(GameState::Loading);
     }
 }
 
@@ -111,16 +155,16 @@ pub struct RandomSeedButton;
 fn start_random_seed(
     mut state: ResMut<NextState<GameState>>,
     mut game_config: ResMut<GameConfig>,
+    mut game_rng: ResMut<GameRng>,
     mut button_query: Query<(&Interaction, &RandomSeedButton)>,
-    mut commands: Commands,
-    menu_query: Query<Entity, With<Menu>>
 ) {
     for (interaction, _) in button_query.iter_mut() {
         if *interaction == Interaction::Pressed {
-            let mut rand = rand::thread_rng();
-            game_config.seed = rand.gen_range(0..1000000);
-            state.set(GameState::Playing);
-            despawn_menu(&mut commands, &menu_query)
+            // Picking the seed is not part of the game, it comes from the OS and is printed to replay the run
+            game_config.seed = rand::thread_rng().gen_range(0..1000000);
+            *game_rng = GameRng::new(game_config.seed);
+            println!("Starting with seed {}", game_config.seed);
+            state.set(GameState::Loading);
         }
     }
 }
\ No newline at end of file
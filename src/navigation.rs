@@ -0,0 +1,245 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use bevy::prelude::*;
+use crate::archetypes::EnnemyArchetypes;
+use crate::collisions::*;
+use crate::config::GameConfig;
+use crate::constants::*;
+use crate::{GameState, TickSet};
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavGrid>()
+            .add_systems(FixedUpdate, update_nav_grid.after(TickSet::Player).before(TickSet::Ennemies).run_if(in_state(GameState::Playing)));
+    }
+}
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+// Steps against an obstacle cost more, so a path keeps its distance whenever there is room
+const NEAR_COST: u32 = 30;
+
+type Cell = (i32, i32);
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Ground {
+    Free,
+    // An ennemy standing here touches an obstacle, it can still squeeze through
+    Near,
+    // Inside an obstacle
+    Solid,
+}
+
+// Walkable cells of the map for an ennemy: a cell is free when the biggest ennemy centered on it does not
+// touch a tree, a grave, the tower or a visible sanctuary. Rebuilt when one of them changes
+#[derive(Resource, Default)]
+pub struct NavGrid {
+    origin: Vec2,
+    width: i32,
+    height: i32,
+    ground: Vec<Ground>,
+    obstacles: HashSet<Entity>,
+}
+
+impl NavGrid {
+    fn rebuild<'a>(&mut self, map_size: f32, ennemy_size: Vec2, obstacles: impl Iterator<Item = (Entity, &'a CollisionComponent)>) {
+        let cells = (map_size / NAV_CELL_SIZE).ceil() as i32;
+        self.origin = Vec2::splat(-map_size / 2.);
+        self.width = cells;
+        self.height = cells;
+        self.ground = vec![Ground::Free; (cells * cells) as usize];
+        self.obstacles.clear();
+
+        let ennemy = Shape::Rect { w: ennemy_size.x + NAV_CLEARANCE * 2., h: ennemy_size.y + NAV_CLEARANCE * 2. };
+        let point = Shape::Circle { radius: 0. };
+        for (entity, obstacle) in obstacles {
+            self.obstacles.insert(entity);
+            let (x, y, w, h) = obstacle.get_hitbox();
+            let reach = Vec2::new(w, h) / 2. + ennemy.size() / 2.;
+            let min = self.cell_at(Vec2::new(x, y) - reach);
+            let max = self.cell_at(Vec2::new(x, y) + reach);
+            for cell_x in min.0..=max.0 {
+                for cell_y in min.1..=max.1 {
+                    if let Some(index) = self.index((cell_x, cell_y)) {
+                        let center = self.center((cell_x, cell_y));
+                        let ground = if are_overlapping(Collider::new(center, point), obstacle.collider()) {
+                            Ground::Solid
+                        } else if are_overlapping(Collider::new(center, ennemy), obstacle.collider()) {
+                            Ground::Near
+                        } else {
+                            Ground::Free
+                        };
+                        self.ground[index] = self.ground[index].max(ground);
+                    }
+                }
+            }
+        }
+    }
+
+    fn cell_at(&self, position: Vec2) -> Cell {
+        let cell = ((position - self.origin) / NAV_CELL_SIZE).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+
+    fn center(&self, cell: Cell) -> Vec2 {
+        self.origin + (Vec2::new(cell.0 as f32, cell.1 as f32) + 0.5) * NAV_CELL_SIZE
+    }
+
+    fn index(&self, cell: Cell) -> Option<usize> {
+        let inside = cell.0 >= 0 && cell.0 < self.width && cell.1 >= 0 && cell.1 < self.height;
+        inside.then_some((cell.1 * self.width + cell.0) as usize)
+    }
+
+    fn ground(&self, cell: Cell) -> Ground {
+        self.index(cell).map_or(Ground::Solid, |index| self.ground[index])
+    }
+
+    fn is_clear(&self, cell: Cell) -> bool {
+        self.ground(cell) == Ground::Free
+    }
+
+    fn is_solid(&self, cell: Cell) -> bool {
+        self.ground(cell) == Ground::Solid
+    }
+
+    // Waypoints from one position to another around the obstacles, the last one being the goal itself.
+    // When the goal is out of reach, or too far for the search budget, the path leads as close as it got.
+    // None when the search could not get any closer than the start
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let start = self.cell_at(from);
+        let goal = self.cell_at(to);
+        let (Some(start_index), Some(goal_index)) = (self.index(start), self.index(goal)) else {
+            return None;
+        };
+
+        // Ties go to the cell closest to the goal, so open ground does not get explored all around
+        let mut open = BinaryHeap::new();
+        // Cost so far and previous cell of every cell reached
+        let mut reached: HashMap<usize, (u32, usize)> = HashMap::new();
+        reached.insert(start_index, (0, start_index));
+        open.push(Reverse((octile_distance(start, goal), octile_distance(start, goal), start_index)));
+
+        // Cell closest to the goal reached so far
+        let mut closest = (octile_distance(start, goal), start_index);
+        let mut expanded = 0;
+        while let Some(Reverse((_, remaining, index))) = open.pop() {
+            if index == goal_index {
+                return Some(self.smooth(self.walk_back(&reached, goal_index), from, to));
+            }
+            closest = closest.min((remaining, index));
+            expanded += 1;
+            if expanded > NAV_MAX_EXPANDED_CELLS {
+                break;
+            }
+
+            let cell = ((index as i32) % self.width, (index as i32) / self.width);
+            let cost = reached[&index].0;
+            for (dx, dy) in NEIGHBOURS {
+                let next = (cell.0 + dx, cell.1 + dy);
+                // The player is smaller than an ennemy and can stand in a cell whose center is inside an obstacle
+                if self.is_solid(next) && next != goal {
+                    continue;
+                }
+                // No cutting the corner of an obstacle
+                if dx != 0 && dy != 0 && (self.is_solid((cell.0 + dx, cell.1)) || self.is_solid((cell.0, cell.1 + dy))) {
+                    continue;
+                }
+                let next_index = (next.1 * self.width + next.0) as usize;
+                let step_cost = if dx != 0 && dy != 0 { DIAGONAL_COST } else { STRAIGHT_COST };
+                let next_cost = cost + step_cost + if self.is_clear(next) { 0 } else { NEAR_COST };
+                let shorter = match reached.get(&next_index) {
+                    Some((known, _)) => next_cost < *known,
+                    None => true,
+                };
+                if shorter {
+                    reached.insert(next_index, (next_cost, index));
+                    let remaining = octile_distance(next, goal);
+                    open.push(Reverse((next_cost + remaining, remaining, next_index)));
+                }
+            }
+        }
+
+        let (_, closest_index) = closest;
+        (closest_index != start_index).then(|| {
+            let cells = self.walk_back(&reached, closest_index);
+            let end = *cells.last().unwrap();
+            self.smooth(cells, from, end)
+        })
+    }
+
+    fn walk_back(&self, reached: &HashMap<usize, (u32, usize)>, goal_index: usize) -> Vec<Vec2> {
+        let mut cells = vec![goal_index];
+        let mut index = goal_index;
+        while reached[&index].1 != index {
+            index = reached[&index].1;
+            cells.push(index);
+        }
+        cells.reverse();
+        cells.into_iter()
+            .map(|index| self.center(((index as i32) % self.width, (index as i32) / self.width)))
+            .collect()
+    }
+
+    // Keeps only the waypoints where the path has to turn: from each kept waypoint,
+    // goes straight to the farthest one still in sight
+    fn smooth(&self, cells: Vec<Vec2>, from: Vec2, to: Vec2) -> Vec<Vec2> {
+        let mut points = cells;
+        points[0] = from;
+        *points.last_mut().unwrap() = to;
+
+        let mut smoothed = Vec::new();
+        let mut anchor = 0;
+        while anchor < points.len() - 1 {
+            let next = (anchor + 1..points.len()).rev()
+                .find(|next| *next == anchor + 1 || self.is_clear_between(points[anchor], points[*next]))
+                .unwrap();
+            smoothed.push(points[next]);
+            anchor = next;
+        }
+        smoothed
+    }
+
+    // Every cell the segment goes through is clear, its two ends excepted
+    fn is_clear_between(&self, from: Vec2, to: Vec2) -> bool {
+        let (start, end) = (self.cell_at(from), self.cell_at(to));
+        let steps = (from.distance(to) / (NAV_CELL_SIZE / 4.)).ceil() as usize;
+        (1..steps)
+            .map(|step| self.cell_at(from.lerp(to, step as f32 / steps as f32)))
+            .all(|cell| cell == start || cell == end || self.is_clear(cell))
+    }
+}
+
+const NEIGHBOURS: [(i32, i32); 8] = [(0, 1), (1, 0), (0, -1), (-1, 0), (1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+fn octile_distance(a: Cell, b: Cell) -> u32 {
+    let dx = (a.0 - b.0).unsigned_abs();
+    let dy = (a.1 - b.1).unsigned_abs();
+    STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+}
+
+fn is_static_obstacle(collision_component: &CollisionComponent) -> bool {
+    let filter = collision_component.collision_filter();
+    filter.is_solid() && filter.layers.intersects(Layers::TERRAIN | Layers::STRUCTURE) && filter.interacts_with(&CollisionFilter::ENNEMY)
+}
+
+fn update_nav_grid(
+    mut nav_grid: ResMut<NavGrid>,
+    colliders: Query<(Entity, &CollisionComponent)>,
+    changed: Query<&CollisionComponent, Changed<CollisionComponent>>,
+    mut removed: RemovedComponents<CollisionComponent>,
+    game_config: Res<GameConfig>,
+    ennemy_archetypes: Res<EnnemyArchetypes>,
+) {
+    // A sanctuary being revealed changes from hidden to an obstacle
+    let obstacles_changed = changed.iter().any(|collision_component| {
+        is_static_obstacle(collision_component) || collision_component.collision_filter().is_hidden()
+    });
+    // Every removal read, so none is left for the next tick
+    let obstacles_removed = removed.iter().filter(|entity| nav_grid.obstacles.contains(entity)).count() > 0;
+    if obstacles_changed || obstacles_removed || nav_grid.ground.is_empty() {
+        nav_grid.rebuild(game_config.map_size, ennemy_archetypes.largest_hitbox(), colliders.iter().filter(|(_, collision_component)| is_static_obstacle(collision_component)));
+    }
+}
@@ -1,6 +1,15 @@
+use std::path::Path;
+
 use bevy::{prelude::*, app::AppExit};
 
 use crate::{GameState, buttons::create_button, loading::create_restart_button};
+use crate::config::GameConfig;
+use crate::constants::*;
+use crate::ennemies::Ennemy;
+use crate::player::Player;
+use crate::save::SaveGame;
+use crate::spawners::SpawnDirector;
+use crate::structures::{Sanctuary, Tower};
 
 pub struct PausePlugin;
 
@@ -8,7 +17,7 @@ impl Plugin for PausePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Paused), pause_menu)
         .add_systems(Update, show_pause.run_if(in_state(GameState::Playing).or_else(in_state(GameState::Paused))))
-        .add_systems(Update, (quit, resume).run_if(in_state(GameState::Paused)));
+        .add_systems(Update, (quit, resume, save).run_if(in_state(GameState::Paused)));
     }
 }
 
@@ -40,6 +49,9 @@ pub struct QuitButton;
 #[derive(Component)]
 pub struct ResumeButton;
 
+#[derive(Component)]
+pub struct SaveButton;
+
 fn pause_menu(
     mut commands: Commands,
     asset_server: Res<AssetServer>, 
@@ -64,6 +76,9 @@ fn pause_menu(
         .with_children(|parent| {
             create_button(parent, "Resume", ResumeButton, &asset_server)
         })
+        .with_children(|parent| {
+            create_button(parent, "Save", SaveButton, &asset_server)
+        })
         .with_children(|parent| {
             create_restart_button(parent, &asset_server)
         })
@@ -99,3 +114,24 @@ fn resume(
         }
     }
 }
+
+fn save(
+    button_query: Query<&Interaction, (Changed<Interaction>, With<SaveButton>)>,
+    game_config: Res<GameConfig>,
+    player_query: Query<&Player>,
+    tower_query: Query<&Tower>,
+    sanctuary_query: Query<&Sanctuary>,
+    ennemy_query: Query<&Ennemy>,
+    spawn_director: Res<SpawnDirector>,
+) {
+    for interaction in button_query.iter() {
+        if *interaction == Interaction::Pressed {
+            let result = SaveGame::capture(&game_config, &player_query, &tower_query, &sanctuary_query, &ennemy_query, &spawn_director)
+                .and_then(|save| save.write(Path::new(SAVE_PATH)));
+            match result {
+                Ok(()) => println!("Game saved to {}", SAVE_PATH),
+                Err(err) => println!("Save failed: {}", err),
+            }
+        }
+    }
+}
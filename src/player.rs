@@ -1,43 +1,55 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use bevy::prelude::*;
-use crate::GameConfig;
+use serde::{Deserialize, Serialize};
 use crate::collisions;
 use crate::constants::*;
 use crate::collisions::*;
 use crate::ennemies;
 use crate::ennemies::*;
 use crate::entitypattern::*;
-use crate::structures;
-use crate::structures::*;
 use crate::setup::*;
-use crate::GameState;
+use crate::headless::*;
+use crate::controls::*;
+use crate::config::GameConfig;
+use crate::animation::*;
+use crate::animation::AnimationClip;
+use crate::atlases::AtlasRegistry;
+use crate::knockback::{apply_hit_reaction, HitReaction};
+use crate::save::PlayerSave;
+use crate::spatial::SpatialIndex;
+use crate::triggers::TriggerVolume;
+use crate::{GameState, TickSet};
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnExit(GameState::Menu), spawn_player)
-        .add_systems(OnExit(GameState::Loading), spawn_player)
-            .add_systems(Update, (player_move, 
-                                                    update_player_pos, 
+        app.add_systems(OnExit(GameState::Loading), spawn_player)
+            .add_systems(FixedUpdate, (save_previous_pos::<Player>,
+                                                    recover_stamina,
+                                                    update_player_state,
                                                     player_facing_direction, 
-                                                    update_player_sprite,
-                                                    tower_detection,
-                                                    sanctuary_detection,
+                                                    player_dodge,
+                                                    player_move, 
+                                                    apply_hit_reaction::<Player>,
+                                                    update_collision,
+                                                    update_player_animation,
+                                                    player_attack,
+                                                    switch_to_game_over
+                                                ).chain().in_set(TickSet::Player).run_if(in_state(GameState::Playing)))
+            .add_systems(Update, (update_player_pos, 
+                                                    update_player_sprite.run_if(is_rendering),
                                                     background_elements_transparency,
                                                     update_hitbox_pos,
                                                     // update_hitbox_visibility,
-                                                    ennemy_detection,
-                                                    update_collision,
-                                                    update_player_state,
-                                                    slide_out_of_collision,
-                                                    switch_to_game_over
                                                 ).run_if(in_state(GameState::Playing)));
     }
 }
 
 
+// Recovery between two combos
 #[derive(Component)]
 pub struct AttackDelay {
     pub timer: Timer,
@@ -51,14 +63,56 @@ impl AttackDelay {
     }
 }
 
+// Where the player is at in a chain of swings
+#[derive(Component, Default)]
+pub struct Combo {
+    step: usize, // swings of the chain so far, 0 outside of a combo
+    buffered: bool, // attack pressed during the current swing, the next one follows right after it
+    window_left: f32, // seconds left to chain the next swing once this one is over
+}
+
+// Spent by sprinting, blocking a blow and dodging, it comes back once the player has rested a little.
+// Running out of it leaves the player exhausted, unable to sprint, block or dodge for a while
+pub struct Stamina {
+    current: f32,
+    max: f32,
+    regen: f32,
+    rest_left: f32, // seconds before it starts coming back
+    exhausted_left: f32,
+}
+
+impl Stamina {
+    pub fn new(max: f32, regen: f32) -> Self {
+        Stamina { current: max, max, regen, rest_left: 0., exhausted_left: 0. }
+    }
+
+    pub fn ratio(&self) -> f32 {
+        self.current / self.max
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted_left > 0.
+    }
+
+    fn spend(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.);
+        self.rest_left = PLAYER_STAMINA_REGEN_DELAY;
+        if self.current <= 0. && !self.is_exhausted() {
+            self.exhausted_left = PLAYER_EXHAUSTION_DURATION;
+            println!("Player is exhausted");
+        }
+    }
 
-enum InteractionType {
-    Tower,
-    Sanctuary,
-    Ennemy,
+    fn recover(&mut self, delta: f32) {
+        self.exhausted_left = (self.exhausted_left - delta).max(0.);
+        self.rest_left = (self.rest_left - delta).max(0.);
+        if self.rest_left <= 0. {
+            self.current = (self.current + self.regen * delta).min(self.max);
+        }
+    }
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum PlayerState {
     Idle,
     Moving,
@@ -72,52 +126,63 @@ pub enum PlayerState {
     Dead
 }
 
+impl AnimationState for PlayerState {
+    const ALL: &'static [PlayerState] = &[PlayerState::Idle, PlayerState::Moving, PlayerState::Sprinting, PlayerState::Attacking, PlayerState::Blocking,
+        PlayerState::Damaged, PlayerState::Healing, PlayerState::Dying, PlayerState::Hiding, PlayerState::Dead];
+}
+
+// The clips of player.png, for when there is no assets/animations/player.ron
+impl Default for AnimationSet<PlayerState> {
+    fn default() -> Self {
+        let clip = |frames: Frames, frame_time: f32, mode: PlayMode, next: Option<PlayerState>| AnimationClip {
+            sheet: "player.png".to_string(), frames, frame_time, mode, next, events: Vec::new(),
+        };
+        let range = |first: usize, count: usize| Frames::Range { first, count };
+        AnimationSet {
+            frame_size: (PLAYER_SPRITE_SIZE, PLAYER_SPRITE_SIZE),
+            sheets: HashMap::from([("player.png".to_string(), SheetLayout { columns: 8, rows: 9, padding: (0., 0.), offset: (0., 1.) })]),
+            clips: HashMap::from([
+                (PlayerState::Idle, clip(Frames::List(vec![0, 1, 8, 9]), 0.4, PlayMode::Loop, None)),
+                (PlayerState::Moving, clip(range(16, 4), 0.2, PlayMode::Loop, None)),
+                (PlayerState::Sprinting, clip(range(24, 8), 0.1, PlayMode::Loop, None)),
+                (PlayerState::Attacking, AnimationClip {
+                    events: vec![FrameEvent { frame: 2, name: "hit".to_string() }],
+                    ..clip(range(64, 7), 0.1, PlayMode::Once, Some(PlayerState::Idle))
+                }),
+                (PlayerState::Blocking, clip(Frames::List(vec![2]), 1., PlayMode::Loop, None)),
+                (PlayerState::Damaged, clip(range(48, 3), 0.15, PlayMode::Once, Some(PlayerState::Idle))),
+                (PlayerState::Healing, clip(range(3, 3), 0.4, PlayMode::LoopFor(3.), Some(PlayerState::Idle))),
+                (PlayerState::Dying, clip(range(56, 8), 1. / 8., PlayMode::Once, Some(PlayerState::Dead))),
+                (PlayerState::Hiding, clip(Frames::List(vec![35]), 1., PlayMode::Loop, None)),
+                (PlayerState::Dead, clip(Frames::List(vec![63]), 1., PlayMode::Loop, None)),
+            ]),
+        }
+    }
+}
+
 
 #[derive(Component)]
 pub struct Player {
     self_entity: EntityPatern,
     state: PlayerState,
-
-    idle_frame_counter: usize,
-    idle_frame_time: f32,
-
-    attack_frame_counter: usize,
-    attack_frame_time: f32,
-
-    sprint_frame_counter: usize,
-    sprint_frame_time: f32,
-
-    walk_frame_counter: usize,
-    walk_frame_time: f32,
-
-    healing_frame_counter: usize,
-    healing_frame_time: f32,
-    healing_duration_elapsed: f32,
-
-    damaged_frame_counter: usize,
-    damaged_frame_time: f32,
-    damaged_duration_elapsed: f32,
-
-    dying_frame_counter: usize,
-    dying_frame_time: f32,
-    dying_duration_elapsed: f32,
+    damage: i32,
+    stamina: Stamina,
 }
 
 impl Player {
-    pub fn new() -> Self {
+    pub fn new(health: i32, damage: i32, stamina: Stamina) -> Self {
         Self { 
-               self_entity: EntityPatern::new(0., 0., PLAYER_HITBOX_WIDTH * 0.8, PLAYER_HITBOX_HEIGHT * 0.8, PLAYER_HEALTH),
+               self_entity: EntityPatern::new(0., 0., PLAYER_HITBOX_WIDTH * 0.8, PLAYER_HITBOX_HEIGHT * 0.8, health),
                state: PlayerState::Idle, 
-               idle_frame_counter: 0, idle_frame_time: 0., 
-               attack_frame_counter: 0, attack_frame_time: 0., 
-               sprint_frame_counter: 0, sprint_frame_time: 0., 
-               walk_frame_counter: 0, walk_frame_time: 0.,
-               healing_frame_counter: 0, healing_frame_time: 0., healing_duration_elapsed: 0.,
-               damaged_frame_counter: 0, damaged_frame_time: 0., damaged_duration_elapsed: 0.,
-                dying_frame_counter: 0, dying_frame_time: 0., dying_duration_elapsed: 0.,
+               damage,
+               stamina,
              }
     }
 
+    pub fn stamina(&self) -> &Stamina {
+        &self.stamina
+    }
+
     pub fn is_aggroable(&self) -> bool {
         if self.is_dead() || self.is_healing() || self.is_dying() || self.is_hiding() {
             return false;
@@ -125,6 +190,16 @@ impl Player {
         true
     }
 
+    // How far ennemies hear the player
+    pub fn noise_radius(&self) -> f32 {
+        match self.state {
+            PlayerState::Sprinting => PLAYER_SPRINT_NOISE,
+            PlayerState::Attacking => PLAYER_ATTACK_NOISE,
+            PlayerState::Moving => PLAYER_WALK_NOISE,
+            _ => 0.,
+        }
+    }
+
     fn can_move(&self) -> bool {
         if self.is_dead() || self.is_healing() || self.is_dying() {
             return false;
@@ -139,7 +214,19 @@ impl Player {
         true
     }
 
-    fn can_interact(&self) -> bool {
+    fn can_sprint(&self) -> bool {
+        !self.stamina.is_exhausted()
+    }
+
+    fn can_block(&self) -> bool {
+        !self.stamina.is_exhausted()
+    }
+
+    fn can_dodge(&self) -> bool {
+        self.can_move() && !self.is_attacking() && !self.is_damaged() && !self.stamina.is_exhausted()
+    }
+
+    pub fn can_interact(&self) -> bool {
         if self.is_dead() || self.is_healing() || self.is_dying() {
             return false;
         }
@@ -174,8 +261,69 @@ impl Player {
         self.state == PlayerState::Hiding
     }
 
-    fn heal(&mut self) {
-        self.self_entity.add_health(SANCTUARY_HEALING);
+    pub fn health(&self) -> i32 {
+        self.self_entity.health()
+    }
+
+    pub fn to_save(&self) -> PlayerSave {
+        PlayerSave {
+            x: self.x(),
+            y: self.y(),
+            health: self.health(),
+            state: self.state,
+            facing_direction: self.facing_direction(),
+        }
+    }
+
+    pub fn restore(&mut self, save: &PlayerSave) {
+        self.self_entity.set_x(save.x);
+        self.self_entity.set_y(save.y);
+        self.self_entity.set_health(save.health);
+        self.self_entity.set_facing(save.facing_direction);
+        self.state = save.state;
+    }
+
+    // The shield only stops a projectile coming from where the player faces
+    pub fn get_shot(&mut self, damage: i32, direction: Vec2) -> bool {
+        if !self.is_aggroable() {
+            return false;
+        }
+        let facing = self.facing_direction().unwrap_or(FacingDirection::Down).to_vec2();
+        if self.is_blocking() && facing.angle_between(-direction).abs() <= PLAYER_SHIELD_HALF_ANGLE.to_radians() {
+            println!("Player blocked a projectile");
+            self.stamina.spend(PLAYER_BLOCK_STAMINA);
+            return false;
+        }
+        self.hurt(damage)
+    }
+
+    // The swing reaches ennemies in front of the player, and any ennemy it stands against
+    fn reaches(&self, ennemy: &Ennemy) -> bool {
+        let (x, y, width, height) = self.get_hitbox();
+        let (ennemy_x, ennemy_y, ennemy_width, ennemy_height) = ennemy.get_hitbox();
+        let position = Vec2::new(x, y);
+        let half_size = Vec2::new(ennemy_width, ennemy_height) / 2.;
+        let closest = position.clamp(Vec2::new(ennemy_x, ennemy_y) - half_size, Vec2::new(ennemy_x, ennemy_y) + half_size);
+        let distance = closest.distance(position);
+        if distance > PLAYER_ATTACK_REACH {
+            return false;
+        }
+        if distance <= width.min(height) / 2. {
+            return true;
+        }
+        let facing = self.facing_direction().unwrap_or(FacingDirection::Down).to_vec2();
+        facing.angle_between(Vec2::new(ennemy_x, ennemy_y) - position).abs() <= PLAYER_ATTACK_HALF_ANGLE.to_radians()
+    }
+
+    fn hurt(&mut self, damage: i32) -> bool {
+        self.take_damage(damage);
+        println!("Player took {} damage", damage);
+        println!("Player health now at {}", self.self_entity.health());
+        true
+    }
+
+    pub fn heal(&mut self, amount: i32) {
+        self.self_entity.add_health(amount);
         println!("Player health now at {}", self.self_entity.health());
         self.state = PlayerState::Healing;
 
@@ -189,12 +337,15 @@ impl Collisionable for Player {
     fn get_hitbox(&self) -> (f32, f32, f32, f32) {
         self.self_entity.get_hitbox()
     }
-    
+
+    fn collision_filter(&self) -> CollisionFilter {
+        CollisionFilter::PLAYER
+    }
 }
 
 impl EntityBehavior for Player {
     fn attack(&mut self, target: &mut dyn EntityBehavior) -> bool {
-        return target.get_attacked(PLAYER_DAMAGE);
+        return target.get_attacked(self.damage);
     }
 
     fn get_attacked(&mut self, damage: i32) -> bool {
@@ -203,12 +354,10 @@ impl EntityBehavior for Player {
         }
         if self.is_blocking() {
             println!("Player blocked the attack");
+            self.stamina.spend(PLAYER_BLOCK_STAMINA);
             return false;
         }
-        self.take_damage(damage);
-        println!("Player took {} damage", damage);
-        println!("Player health now at {}", self.self_entity.health());
-        true
+        self.hurt(damage)
     }
 
     fn take_damage(&mut self, damage: i32) -> bool {
@@ -243,14 +392,6 @@ impl EntityBehavior for Player {
     fn set_facing_direction(&mut self, facing_direction: FacingDirection) {
         self.self_entity.set_facing_direction(facing_direction);
     }
-
-    fn add_x(&mut self, x: f32) {
-        self.self_entity.add_x(x);
-    }
-
-    fn add_y(&mut self, y: f32) {
-        self.self_entity.add_y(y);
-    }
 }
 
 
@@ -258,36 +399,40 @@ impl EntityBehavior for Player {
 pub struct HitBox;
 
 fn spawn_player(mut commands: Commands, 
-    asset_server: Res<AssetServer>, 
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,) 
+    atlases: Res<AtlasRegistry>,
+    animations: Res<AnimationSet<PlayerState>>,
+    game_config: Res<GameConfig>,) 
     {
 
-    let texture_handle = asset_server.load("player.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(PLAYER_SPRITE_SIZE, PLAYER_SPRITE_SIZE), 8, 9, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 1.)));
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    let animator = Animator::new(PlayerState::Idle);
+    let texture_atlas_handle = atlases.atlas(animator.sheet(&animations));
 
-    let player: Player = Player::new();
+    let stamina = Stamina::new(game_config.player_stamina, game_config.player_stamina_regen);
+    let player: Player = Player::new(game_config.player_health, game_config.player_damage, stamina);
     let (x, y) = player.get_pos();
-    let collisioncomponent = CollisionComponent::new(x, y, PLAYER_HITBOX_WIDTH, PLAYER_HITBOX_HEIGHT);
+    let collisioncomponent = CollisionComponent::new(x, y, PLAYER_HITBOX_WIDTH, PLAYER_HITBOX_HEIGHT, CollisionFilter::PLAYER);
 
-    let attack_delay = AttackDelay::new(PLAYER_ATTACK_DELAY);
+    let attack_delay = AttackDelay::new(game_config.player_attack_delay);
     
     let hitbox = player.get_hitbox();
 
-    commands.spawn(SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle.clone(),
-            transform: Transform {
-                translation: Vec3::new(0., 0., 1.),
-                scale: Vec3::new(PLAYER_SPRITE_SCALE, PLAYER_SPRITE_SCALE, Z_LAYER_PLAYER),
-                ..Transform::default()
-            },
-            sprite: TextureAtlasSprite::new(0),
-            ..Default::default()
+    spawn_sprite_sheet(&mut commands, &texture_atlas_handle, TextureAtlasSprite::new(0), Transform {
+            translation: Vec3::new(0., 0., 1.),
+            scale: Vec3::new(PLAYER_SPRITE_SCALE, PLAYER_SPRITE_SCALE, Z_LAYER_PLAYER),
+            ..Transform::default()
         })
         .insert(player)
+        .insert(PreviousPosition::new(x, y))
         .insert(attack_delay)
+        .insert(Combo::default())
+        .insert(HitReaction::new(game_config.player_invulnerability))
+        .insert(animator)
         .insert(collisioncomponent);
 
+    if texture_atlas_handle.is_none() {
+        return;
+    }
+
     commands.spawn(SpriteBundle {
         transform: Transform {
             translation: Vec3::new(0., 0., Z_LAYER_GUI),
@@ -304,10 +449,11 @@ fn spawn_player(mut commands: Commands,
 }
 
 fn update_player_state(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<&mut Player>,
+    controls: Res<Controls>,
+    mut query: Query<(Entity, &mut Player)>,
+    bushes: Query<&TriggerVolume, With<BackgroundObjects>>,
 ) {
-    let mut player = query.single_mut();
+    let (player_entity, mut player) = query.single_mut();
 
     if player.is_attacking() || player.is_dead() || player.is_healing() || player.is_damaged() || player.is_dying() {
         return;
@@ -315,17 +461,18 @@ fn update_player_state(
 
     // CLASSE PAR ORDRE DIMPORTANCE
 
-    if keyboard_input.pressed(KeyCode::ShiftLeft){
+    if controls.pressed(Control::Sprint) && player.can_sprint() {
         player.state = PlayerState::Sprinting;
     }
     
-    else if keyboard_input.pressed(KeyCode::Up) || keyboard_input.pressed(KeyCode::Down) || keyboard_input.pressed(KeyCode::Left) || keyboard_input.pressed(KeyCode::Right) {
+    else if controls.pressed(Control::Up) || controls.pressed(Control::Down) || controls.pressed(Control::Left) || controls.pressed(Control::Right) {
         player.state = PlayerState::Moving;
     } 
-    else if keyboard_input.pressed(KeyCode::E) {
+    else if controls.pressed(Control::Block) && player.can_block() {
         player.state = PlayerState::Blocking;
     }
-    else if keyboard_input.pressed(KeyCode::A) {
+    // Se cacher ne marche que dans un buisson
+    else if controls.pressed(Control::Hide) && bushes.iter().any(|bush| bush.contains(player_entity)) {
         player.state = PlayerState::Hiding;
     }
     else {
@@ -334,70 +481,78 @@ fn update_player_state(
 }
 
 fn player_move(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<&mut Player>,
-    collisionable_query: Query<&CollisionComponent, Without<Player>>,
+    controls: Res<Controls>,
+    mut player_query: Query<(Entity, &mut Player)>,
+    surroundings: Surroundings,
+    fixed_time: Res<FixedTime>,
+    game_config: Res<GameConfig>,
 ) {
     
     let player_speed: f32;
-    let mut player = player_query.single_mut();
+    let (player_entity, mut player) = player_query.single_mut();
 
     if !player.can_move() {
         return;
     }
 
-    if keyboard_input.pressed(KeyCode::ShiftLeft) {
-        player_speed = PLAYER_SPRINT_SPEED;
+    let sprinting = controls.pressed(Control::Sprint) && player.can_sprint();
+    if sprinting {
+        player_speed = game_config.player_sprint_speed * fixed_time.period.as_secs_f32();
 
     } else {
-        player_speed = PLAYER_NORMAL_SPEED;
+        player_speed = game_config.player_normal_speed * fixed_time.period.as_secs_f32();
     }
-    let left_boundary = -((MAP_SIZE / 2.0) - (PLAYER_HITBOX_WIDTH / 2.));
+    let left_boundary = -((game_config.map_size / 2.0) - (PLAYER_HITBOX_WIDTH / 2.));
     let right_boundary = -left_boundary;
     let top_boundary = right_boundary;
     let bottom_boundary = left_boundary;
 
-    let actual_x = player.x();
-    let actual_y = player.y();
-
-    let mut new_x = if keyboard_input.pressed(KeyCode::Left) { player.x() - player_speed }
-                     else if keyboard_input.pressed(KeyCode::Right) { player.x() + player_speed }
-                     else { player.x() };
+    let dx = if controls.pressed(Control::Left) { -player_speed }
+             else if controls.pressed(Control::Right) { player_speed }
+             else { 0. };
+    let dy = if controls.pressed(Control::Down) { -player_speed }
+             else if controls.pressed(Control::Up) { player_speed }
+             else { 0. };
+    let delta = Vec2::new(dx, dy);
 
-    for collidable in collisionable_query.iter() {
-        if player.would_collide(new_x, player.y(), collidable){ 
-            new_x = actual_x;
-        }
-    }
+    // Glisse le long des obstacles au lieu de s'y bloquer
+    let hitbox = player.get_hitbox();
+    let obstacles = surroundings.obstacles_on_the_way(player_entity, hitbox, player.collision_filter(), delta);
+    let position = move_and_slide(player.collider(), delta, &obstacles)
+        .clamp(Vec2::new(left_boundary, bottom_boundary), Vec2::new(right_boundary, top_boundary));
 
-    if new_x > right_boundary {
-        new_x = actual_x;
-    } else if new_x < left_boundary {
-        new_x = actual_x;
+    player.set_x(position.x);
+    player.set_y(position.y);
+    if sprinting && delta != Vec2::ZERO {
+        player.stamina.spend(PLAYER_SPRINT_STAMINA * fixed_time.period.as_secs_f32());
     }
+}
 
-    let mut new_y = if keyboard_input.pressed(KeyCode::Down) { player.y() - player_speed }
-                     else if keyboard_input.pressed(KeyCode::Up) { player.y() + player_speed }
-                     else { player.y() };
-
-    for collidable in collisionable_query.iter() {
-        if player.would_collide(player.x(), new_y, collidable){ 
-            new_y = actual_y;
-        }
-    }
+fn recover_stamina(
+    mut query: Query<&mut Player>,
+    fixed_time: Res<FixedTime>,
+) {
+    let mut player = query.single_mut();
+    player.stamina.recover(fixed_time.period.as_secs_f32());
+}
 
-    if new_y > top_boundary {
-        new_y = actual_y;
-    } else if new_y < bottom_boundary {
-        new_y = actual_y;
+// A quick dash where the player faces, moved like a knockback so it stops against obstacles
+fn player_dodge(
+    controls: Res<Controls>,
+    mut query: Query<(&mut Player, &mut HitReaction)>,
+) {
+    let (mut player, mut reaction) = query.single_mut();
+    if !controls.just_pressed(Control::Dodge) || !player.can_dodge() {
+        return;
     }
-    player.set_x(new_x);
-    player.set_y(new_y);
+    player.stamina.spend(PLAYER_DODGE_STAMINA);
+    let direction = player.facing_direction().unwrap_or(FacingDirection::Down).to_vec2();
+    reaction.push(direction, PLAYER_DODGE_SPEED);
 }
 
 
 fn player_facing_direction(
-    keyboard_input: Res<Input<KeyCode>>,
+    controls: Res<Controls>,
     mut query: Query<&mut Player>,
 ) {
     let mut player = query.single_mut();
@@ -405,142 +560,61 @@ fn player_facing_direction(
     if !player.can_change_facing_direction() {
         return;
     }
-    if keyboard_input.pressed(KeyCode::Left) && keyboard_input.pressed(KeyCode::Up) {
+    if controls.pressed(Control::Left) && controls.pressed(Control::Up) {
         player.set_facing_direction(FacingDirection::TopLeft)
     }
-    else if keyboard_input.pressed(KeyCode::Left) && keyboard_input.pressed(KeyCode::Down) {
+    else if controls.pressed(Control::Left) && controls.pressed(Control::Down) {
         player.set_facing_direction(FacingDirection::BottomLeft)
     }
-    else if keyboard_input.pressed(KeyCode::Right) && keyboard_input.pressed(KeyCode::Up) {
+    else if controls.pressed(Control::Right) && controls.pressed(Control::Up) {
         player.set_facing_direction(FacingDirection::TopRight)
     }
-    else if keyboard_input.pressed(KeyCode::Right) && keyboard_input.pressed(KeyCode::Down) {
+    else if controls.pressed(Control::Right) && controls.pressed(Control::Down) {
         player.set_facing_direction(FacingDirection::BottomRight)
     }
-    else if keyboard_input.pressed(KeyCode::Left) {
+    else if controls.pressed(Control::Left) {
         player.set_facing_direction(FacingDirection::Left)
     }
-    else if keyboard_input.pressed(KeyCode::Right) {
+    else if controls.pressed(Control::Right) {
         player.set_facing_direction(FacingDirection::Right)
     }
-    else if keyboard_input.pressed(KeyCode::Up) {
+    else if controls.pressed(Control::Up) {
         player.set_facing_direction(FacingDirection::Up)
     }
-    else if keyboard_input.pressed(KeyCode::Down) {
+    else if controls.pressed(Control::Down) {
         player.set_facing_direction(FacingDirection::Down)
     }
 }
 
 
-fn update_player_sprite(
-    mut query: Query<(&mut Player, &mut TextureAtlasSprite)>,
-    time: Res<Time>,
+// Clips that are over hand the player over to their next state
+fn update_player_animation(
+    mut query: Query<(Entity, &mut Player, &mut Animator<PlayerState>)>,
+    animations: Res<AnimationSet<PlayerState>>,
+    mut animation_events: EventWriter<AnimationEvent>,
+    fixed_time: Res<FixedTime>,
 ) {
-    let (mut player, mut texture) = query.single_mut();
-    match player.state {
-        PlayerState::Idle => {
-            player.idle_frame_time += time.delta_seconds();
-
-            if player.idle_frame_time >= 0.4 {
-                player.idle_frame_counter = (player.idle_frame_counter + 1) % 4;
-                player.idle_frame_time = 0.0; 
-            }
-            texture.index = match player.idle_frame_counter {
-                0 => 0,
-                1 => 1,
-                2 => 8,
-                3 => 9,
-                _ => 0,
-            };
-        },
-        PlayerState::Blocking => texture.index = 2,
-        PlayerState::Moving => {
-            player.walk_frame_time += time.delta_seconds();
-
-            if player.walk_frame_time >= 0.2 {
-                player.walk_frame_counter = (player.walk_frame_counter + 1) % 4;
-                player.walk_frame_time = 0.0; 
-            }
-            texture.index = 16 + player.walk_frame_counter;
-        },
-        PlayerState::Sprinting => {
-            player.sprint_frame_time += time.delta_seconds();
-
-            if player.sprint_frame_time >= 0.1 {
-                player.sprint_frame_counter = (player.sprint_frame_counter + 1) % 8;
-                player.sprint_frame_time = 0.0; 
-            }
-            texture.index = 24 + player.sprint_frame_counter;
-
-        },
-        PlayerState::Attacking => {
-            player.attack_frame_time += time.delta_seconds();
-
-            // Changer de frame toutes les 0.1 secondes (ou selon votre choix)
-            if player.attack_frame_time >= 0.1 {
-                player.attack_frame_counter = player.attack_frame_counter + 1;
-                player.attack_frame_time = 0.0; // Réinitialiser le temps écoulé
-            }
-
-            // Mettre à jour l'index de la texture
-            texture.index = 64 + player.attack_frame_counter;
-            if player.attack_frame_counter >= 7 {
-                player.state = PlayerState::Idle;
-                player.attack_frame_counter = 0;
-            }
-        },
-
-        PlayerState::Dying => {
-            player.dying_frame_time += time.delta_seconds();
-            player.dying_duration_elapsed += time.delta_seconds();
-
-            if player.dying_frame_time >= 1./8. {
-                player.dying_frame_counter = (player.dying_frame_counter + 1) % 8;
-                player.dying_frame_time = 0.0; 
-            }
-            texture.index = 56 + player.dying_frame_counter;
-            if player.dying_duration_elapsed >= 1. {
-                player.state = PlayerState::Dead;
-                player.dying_duration_elapsed = 0.;
-            }
-        },
-        
-        PlayerState::Dead => {
-            texture.index = 63;
-        },
-        PlayerState::Healing => {
-            player.healing_frame_time += time.delta_seconds();
-            player.healing_duration_elapsed += time.delta_seconds();
-
-            if player.healing_frame_time >= 0.4 {
-                player.healing_frame_counter = (player.healing_frame_counter + 1) % 3;
-                player.healing_frame_time = 0.0; 
-            }
-            texture.index = 3 + player.healing_frame_counter;
-            if player.healing_duration_elapsed >= 3. {
-                player.state = PlayerState::Idle;
-                player.healing_duration_elapsed = 0.;
-            }
-            
-        },
-        PlayerState::Damaged => {
-            player.damaged_frame_time += time.delta_seconds();
-            player.damaged_duration_elapsed += time.delta_seconds();
+    let (entity, mut player, mut animator) = query.single_mut();
+    animator.play(player.state);
+    let next = animator.advance(&animations, fixed_time.period.as_secs_f32(), |name| {
+        animation_events.send(AnimationEvent { entity, name: name.to_string() });
+    });
+    if let Some(next) = next {
+        player.state = next;
+        animator.play(next);
+    }
+}
 
-            if player.damaged_frame_time >= 0.15 {
-                player.damaged_frame_counter = (player.damaged_frame_counter + 1) % 3;
-                player.damaged_frame_time = 0.0; 
-            }
-            texture.index = 48 + player.damaged_frame_counter;
-            if player.damaged_duration_elapsed >= 0.15*3. {
-                player.state = PlayerState::Idle;
-                player.damaged_duration_elapsed = 0.;
-            }
-        }
-        PlayerState::Hiding => {
-            texture.index = 35;
-        },
+fn update_player_sprite(
+    mut query: Query<(&Animator<PlayerState>, &mut TextureAtlasSprite, &mut Handle<TextureAtlas>)>,
+    animations: Res<AnimationSet<PlayerState>>,
+    atlases: Res<AtlasRegistry>,
+) {
+    let (animator, mut sprite, mut texture) = query.single_mut();
+    if let Some(handle) = atlases.atlas(animator.sheet(&animations)).filter(|handle| *handle != *texture) {
+        *texture = handle;
     }
+    sprite.index = animator.atlas_index(&animations);
 }
 
 fn switch_to_game_over(
@@ -553,13 +627,13 @@ fn switch_to_game_over(
     }
 }
 
-fn update_player_pos(
-    mut query: Query<(&mut Player, &mut Transform)>,
+pub fn update_player_pos(
+    mut query: Query<(&Player, &PreviousPosition, &mut Transform)>,
+    fixed_time: Res<FixedTime>,
 ) {
 
-    let (player, mut sprite) = query.single_mut();
-    let x = player.x();
-    let y = player.y();
+    let (player, previous, mut sprite) = query.single_mut();
+    let (x, y) = previous.lerp(player.x(), player.y(), interpolation_alpha(&fixed_time));
     sprite.translation.x = x;
     sprite.translation.y = y;
     if let Some(facing_direction) = &player.facing_direction() {
@@ -606,117 +680,80 @@ fn update_collision(
     update_collisionable_pos::<Player>(&mut query);
 }
 
-fn can_interact_with(
-    player: &Player,
-    interaction_type: &InteractionType,
-    x: f32,
-    y: f32,
-    tower: Option<&Tower>,
-    sanctuary: Option<&Sanctuary>,
-    ennemy: Option<&Ennemy>,
-) -> bool {
-
-    if !player.can_interact() {
-        return false;
-    }
-
-    match interaction_type {
-        InteractionType::Tower => {
-            if let Some(t) = tower {
-                player.would_collide(x, y, &CollisionComponent::new_from_component(t))
-            } else {
-                false
-            }
-        }
-        InteractionType::Sanctuary => {
-            if let Some(s) = sanctuary {
-                player.would_collide(x, y, &CollisionComponent::new_from_component(s))
-            } else {
-                false
-            }
+// A combo starts when an ennemy is within the swing and the player has recovered from the last one.
+// Each of its swings lands on the "hit" event of the attack, on every ennemy it reaches that is not
+// still recovering from the last one. Pressing during a swing, or within the window after it, chains the next one
+fn player_attack(
+    mut player_query: Query<(Entity, &mut Player, &mut AttackDelay, &mut Combo)>,
+    mut ennemy_query: Query<(&mut Ennemy, &mut HitReaction)>,
+    spatial_index: Res<SpatialIndex>,
+    controls: Res<Controls>,
+    mut animation_events: EventReader<AnimationEvent>,
+    fixed_time: Res<FixedTime>,
+    game_config: Res<GameConfig>,
+) {
+    let (player_entity, mut player, mut attack_delay, mut combo) = player_query.single_mut();
+    let delta = fixed_time.period.as_secs_f32();
+    attack_delay.timer.tick(fixed_time.period);
+    let hit = animation_events.iter().any(|event| event.entity == player_entity && event.name == "hit");
+    let pressed = controls.just_pressed(Control::Interact);
+
+    let (x, y) = player.get_pos();
+    let is_target = |entity: Entity| ennemy_query.get(entity).is_ok_and(|(ennemy, _)| ennemy.health() > 0 && player.reaches(ennemy));
+
+    if player.is_attacking() {
+        if pressed {
+            combo.buffered = true;
         }
-        InteractionType::Ennemy => {
-            if let Some(e) = ennemy {
-                player.would_collide(x, y, &CollisionComponent::new_from_component(e))
-            } else {
-                false
+        if hit {
+            let damage = (player.damage as f32 * PLAYER_COMBO_DAMAGE[combo.step.max(1) - 1]).round() as i32;
+            let reach = PLAYER_ATTACK_REACH * 2.;
+            let targets: Vec<Entity> = spatial_index.colliders_near(x, y, reach, reach).into_iter()
+                .filter(|entity| is_target(*entity))
+                .collect();
+            for entity in targets {
+                if let Ok((mut ennemy, mut reaction)) = ennemy_query.get_mut(entity) {
+                    if reaction.is_invulnerable() {
+                        continue;
+                    }
+                    let actual_ennemy: &mut ennemies::Ennemy = &mut ennemy;
+                    if actual_ennemy.get_attacked(damage) {
+                        let direction = Vec2::new(actual_ennemy.x() - x, actual_ennemy.y() - y);
+                        reaction.hit(direction, game_config.player_knockback);
+                    }
+                }
             }
         }
+        return;
     }
-}
 
-fn tower_detection(
-    mut player_query: Query<&mut Player>,
-    tower_query: Query<&Tower>,
-    keyboard_input: Res<Input<KeyCode>>,
-    query_sanctuary: Query<&mut Sanctuary>,
-    game_config: Res<GameConfig>,
-    nextstate : ResMut<NextState<GameState>>
-) {
-    let player = player_query.single_mut();
-    for tower in tower_query.iter() {
-        if can_interact_with(&player, &InteractionType::Tower, player.x(), player.y() + 1., Some(tower), None, None) {
-            if keyboard_input.just_pressed(KeyCode::Space) {
-                structures::show_one_sanctuary(query_sanctuary, game_config, nextstate);
-                break;
-            }
+    // Entre deux coups, la fenêtre pour enchaîner se referme
+    if combo.step > 0 {
+        combo.window_left -= delta;
+        let can_chain = combo.step < PLAYER_COMBO_DAMAGE.len() && combo.window_left > 0.;
+        if can_chain && (combo.buffered || pressed) && player.can_interact() && !player.is_damaged() {
+            start_swing(&mut player, &mut combo);
+        } else if !can_chain {
+            *combo = Combo::default();
+            attack_delay.timer.reset();
         }
+        return;
     }
-}
 
-fn sanctuary_detection(
-    mut player_query: Query<&mut Player>,
-    mut sanctuary_query: Query<&mut Sanctuary>,
-    keyboard_input: Res<Input<KeyCode>>,
-) {
-    let mut player = player_query.single_mut();
-    for mut sanctuary in sanctuary_query.iter_mut() {
-        if can_interact_with(&player, &InteractionType::Sanctuary, player.x(), player.y() + 1., None, Some(&sanctuary), None) ||
-            can_interact_with(&player, &InteractionType::Sanctuary, player.x(), player.y() - 1., None, Some(&sanctuary), None) ||
-            can_interact_with(&player, &InteractionType::Sanctuary, player.x() + 1., player.y(), None, Some(&sanctuary), None) ||
-            can_interact_with(&player, &InteractionType::Sanctuary, player.x() - 1., player.y(), None, Some(&sanctuary), None) {
-            if keyboard_input.just_pressed(KeyCode::Space) {
-                if sanctuary.unlock() {  
-                    player.heal();
-                }
-                break;
-            }
-        }
+    if pressed && attack_delay.timer.finished() && player.can_interact() && !player.is_damaged()
+        && spatial_index.nearest(x, y, PLAYER_ATTACK_REACH, is_target).is_some() {
+        start_swing(&mut player, &mut combo);
     }
 }
 
-fn ennemy_detection(
-    mut player_query: Query<(&mut Player, &mut AttackDelay)>,
-    mut ennemy_query: Query<&mut Ennemy>,
-    keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>,
-) {
-    let (mut player, mut attack_delay) = player_query.single_mut();
-    
-    attack_delay.timer.tick(time.delta());
-
-
-    if attack_delay.timer.finished() {
-        if player.is_attacking() {
-            player.state = PlayerState::Idle;
-        }
-        for mut ennemy in ennemy_query.iter_mut() {
-            if can_interact_with(&player, &InteractionType::Ennemy, player.x(), player.y() + PLAYER_ATTACK_RANGE, None, None, Some(&ennemy)) ||
-                can_interact_with(&player, &InteractionType::Ennemy, player.x(), player.y() - PLAYER_ATTACK_RANGE, None, None, Some(&ennemy)) ||
-                can_interact_with(&player, &InteractionType::Ennemy, player.x() + PLAYER_ATTACK_RANGE, player.y(), None, None, Some(&ennemy)) ||
-                can_interact_with(&player, &InteractionType::Ennemy, player.x() - PLAYER_ATTACK_RANGE, player.y(), None, None, Some(&ennemy)) {
-                if keyboard_input.just_pressed(KeyCode::Space) {
-                    let actual_ennemy: &mut ennemies::Ennemy = &mut ennemy;
-                    player.attack(actual_ennemy);
-                    player.state = PlayerState::Attacking;
-                    attack_delay.timer.reset();
-                    break;
-                }
-            }
-        }
-    }
+fn start_swing(player: &mut Player, combo: &mut Combo) {
+    player.state = PlayerState::Attacking;
+    combo.step += 1;
+    combo.buffered = false;
+    combo.window_left = PLAYER_COMBO_WINDOW;
 }
 
+
 pub fn background_elements_transparency(
     mut player_query: Query<&mut Player>,
     mut background_objects: Query<(&mut TextureAtlasSprite, &Transform, &BackgroundObjects)>,
@@ -737,73 +774,10 @@ pub fn background_elements_transparency(
             _ => 0.,
         });
 
-        if collisions::are_overlapping(x, y, PLAYER_HITBOX_WIDTH, PLAYER_HITBOX_HEIGHT, transform.translation.x, transform.translation.y, bg_obj_width, bg_obj_height) {
+        if collisions::are_overlapping(Collider::rect(x, y, PLAYER_HITBOX_WIDTH, PLAYER_HITBOX_HEIGHT), Collider::rect(transform.translation.x, transform.translation.y, bg_obj_width, bg_obj_height)) {
             sprite.color.set_a(0.50);
         } else {
             sprite.color.set_a(1.0);
         }
     }
 }
-
-fn slide_out_of_collision(
-    mut player_query: Query<&mut Player>,
-    mut collisionable_query: Query<&mut CollisionComponent, Without<Player>>,
-) {
-    let mut player = player_query.single_mut();
-    let (orig_x, orig_y) = player.get_pos();
-    
-    // Vérifier si le joueur est actuellement en collision avec quelque chose
-    let mut currently_colliding = false;
-    for collidable in collisionable_query.iter_mut() {
-        if player.would_collide(orig_x, orig_y, &collidable) {
-            currently_colliding = true;
-            break;
-        }
-    }
-    
-    // Si le joueur n'est pas en collision, quitter la fonction
-    if !currently_colliding {
-        return;
-    }
-
-    // Définir les déplacements possibles
-    let possible_moves = [
-        (1., 0.),
-        (0., 1.),
-        (-1., 0.),
-        (0., -1.),
-        (1., 1.),
-        (-1., -1.),
-        (1., -1.),
-        (-1., 1.)
-    ];
-    
-    let mut best_move = (0., 0.);
-    let mut min_collisions = usize::MAX;
-
-    for (dx, dy) in possible_moves.iter() {
-        let new_x = orig_x + dx;
-        let new_y = orig_y + dy;
-        let mut collision_count = 0;
-
-        for collidable in collisionable_query.iter_mut() {
-            if player.would_collide(new_x, new_y, &collidable) {
-                collision_count += 1;
-            }
-        }
-
-        if collision_count == 0 {
-            best_move = (*dx, *dy);
-            break;
-        }
-
-        if collision_count < min_collisions {
-            best_move = (*dx, *dy);
-            min_collisions = collision_count;
-        }
-    }
-
-    // Déplacer le joueur vers la meilleure position trouvée
-    player.set_x(orig_x + best_move.0);
-    player.set_y(orig_y + best_move.1);
-}
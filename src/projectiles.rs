@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use crate::camera::CameraShake;
+use crate::collisions::*;
+use crate::constants::*;
+use crate::ennemies::Ennemy;
+use crate::entitypattern::*;
+use crate::knockback::HitReaction;
+use crate::player::Player;
+use crate::{GameState, TickSet};
+
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, move_projectiles.in_set(TickSet::Projectiles).run_if(in_state(GameState::Playing)))
+            .add_systems(Update, update_projectile_position.run_if(in_state(GameState::Playing)));
+    }
+}
+
+// Flies straight until it hits something its filter accepts or its lifetime runs out,
+// which bounds how far it goes
+#[derive(Component)]
+pub struct Projectile {
+    owner: Entity,
+    position: Vec2,
+    velocity: Vec2,
+    lifetime: f32, // seconds left
+    damage: i32,
+    // Speed whoever it hits is pushed away at, along its flight
+    knockback: f32,
+    filter: CollisionFilter,
+}
+
+impl Projectile {
+    pub fn new(owner: Entity, position: Vec2, velocity: Vec2, lifetime: f32, damage: i32, knockback: f32, filter: CollisionFilter) -> Self {
+        Projectile { owner, position, velocity, lifetime, damage, knockback, filter }
+    }
+}
+
+// Drawn as a thin line along its flight, or a bare transform when there is nothing to draw
+pub fn spawn_projectile(commands: &mut Commands, rendering: bool, projectile: Projectile) {
+    let transform = Transform {
+        translation: projectile.position.extend(Z_LAYER_PROJECTILES),
+        rotation: Quat::from_rotation_z(projectile.velocity.y.atan2(projectile.velocity.x)),
+        ..Default::default()
+    };
+    let previous = PreviousPosition::new(projectile.position.x, projectile.position.y);
+    if rendering {
+        commands.spawn(SpriteBundle {
+            transform,
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(PROJECTILE_LENGTH, PROJECTILE_RADIUS)),
+                color: Color::rgb(0.9, 0.88, 0.8),
+                ..Default::default()
+            },
+            ..Default::default()
+        }).insert((projectile, previous));
+    } else {
+        commands.spawn(TransformBundle::from_transform(transform)).insert((projectile, previous));
+    }
+}
+
+fn move_projectiles(
+    mut commands: Commands,
+    mut projectile_query: Query<(Entity, &mut Projectile, &mut PreviousPosition)>,
+    mut player_query: Query<(&mut Player, &mut HitReaction)>,
+    mut ennemy_query: Query<(&mut Ennemy, &mut HitReaction), Without<Player>>,
+    surroundings: Surroundings,
+    mut shake: EventWriter<CameraShake>,
+    fixed_time: Res<FixedTime>,
+) {
+    let delta = fixed_time.period.as_secs_f32();
+    for (entity, mut projectile, mut previous) in projectile_query.iter_mut() {
+        *previous = PreviousPosition::new(projectile.position.x, projectile.position.y);
+        let collider = Collider::new(projectile.position, Shape::Circle { radius: PROJECTILE_RADIUS });
+        let step = projectile.velocity * delta;
+
+        let Some((target, time)) = surroundings.first_hit(projectile.owner, collider, projectile.filter, step) else {
+            projectile.position += step;
+            projectile.lifetime -= delta;
+            if projectile.lifetime <= 0. {
+                commands.entity(entity).despawn();
+            }
+            continue;
+        };
+
+        // Stopped by whatever it hit, only the living that are not recovering from a hit take damage
+        projectile.position += step * time;
+        if let Ok((mut player, mut reaction)) = player_query.get_mut(target) {
+            if !reaction.is_invulnerable() && player.get_shot(projectile.damage, projectile.velocity) {
+                reaction.hit(projectile.velocity, projectile.knockback);
+                shake.send(CameraShake { trauma: CAMERA_DAMAGE_TRAUMA });
+            }
+        } else if let Ok((mut ennemy, mut reaction)) = ennemy_query.get_mut(target) {
+            let actual_ennemy: &mut Ennemy = &mut ennemy;
+            if !reaction.is_invulnerable() && actual_ennemy.get_attacked(projectile.damage) {
+                reaction.hit(projectile.velocity, projectile.knockback);
+            }
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+fn update_projectile_position(
+    mut query: Query<(&mut Transform, &PreviousPosition, &Projectile)>,
+    fixed_time: Res<FixedTime>,
+) {
+    let alpha = interpolation_alpha(&fixed_time);
+    for (mut transform, previous, projectile) in query.iter_mut() {
+        let (x, y) = previous.lerp(projectile.position.x, projectile.position.y, alpha);
+        transform.translation = Vec3::new(x, y, Z_LAYER_PROJECTILES);
+    }
+}
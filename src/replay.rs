@@ -0,0 +1,312 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use crate::collisions::Collisionable;
+use crate::constants::*;
+use crate::controls::Controls;
+use crate::ennemies::Ennemy;
+use crate::player::Player;
+use crate::save::PendingLoad;
+use crate::structures::Sanctuary;
+use crate::config::GameConfig;
+use crate::{GameState, TickSet};
+
+const REPLAY_HEADER: &str = "zelda-replay 2";
+
+pub enum ReplayMode {
+    Record(PathBuf),
+    Play(Replay),
+}
+
+pub struct ReplayPlugin {
+    pub mode: ReplayMode,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        match &self.mode {
+            ReplayMode::Record(path) => {
+                app.insert_resource(ReplayRecorder::new(path.clone()))
+                    .add_systems(OnExit(GameState::Loading), start_recording)
+                    .add_systems(FixedUpdate, record_tick.in_set(TickSet::Record).run_if(in_state(GameState::Playing)));
+            }
+            ReplayMode::Play(replay) => {
+                app.insert_resource(replay.clone())
+                    .add_systems(Startup, start_replay)
+                    .add_systems(FixedUpdate, (
+                        play_tick.in_set(TickSet::Input),
+                        verify_tick.in_set(TickSet::Record),
+                    ).run_if(in_state(GameState::Playing)));
+            }
+        }
+    }
+}
+
+// Config and per-tick inputs of a recorded run, with the world checksums expected along the way
+#[derive(Resource, Clone)]
+pub struct Replay {
+    config: GameConfig,
+    inputs: Vec<Controls>,
+    checksums: Vec<(u32, u64)>,
+    tick: u32,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read replay {}: {}", path.display(), err))?;
+        let mut lines = content.lines().enumerate();
+
+        match lines.next() {
+            Some((_, REPLAY_HEADER)) => {},
+            _ => return Err(format!("{} is not a replay file (expected \"{}\")", path.display(), REPLAY_HEADER)),
+        }
+
+        // The config the run was recorded with, on a single line
+        let config: GameConfig = match lines.next().and_then(|(_, line)| line.strip_prefix("config ")) {
+            Some(config) => ron::from_str(config).map_err(|err| format!("{}:2: invalid config: {}", path.display(), err))?,
+            None => return Err(format!("{}:2: missing config", path.display())),
+        };
+        config.validate().map_err(|err| format!("{}:2: invalid config: {}", path.display(), err))?;
+
+        let mut inputs = Vec::new();
+        let mut checksums = Vec::new();
+        for (i, line) in lines {
+            let invalid = || format!("{}:{}: invalid line \"{}\"", path.display(), i + 1, line);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["checksum", tick, checksum] => checksums.push((
+                    tick.parse().map_err(|_| invalid())?,
+                    u64::from_str_radix(checksum, 16).map_err(|_| invalid())?,
+                )),
+                [pressed, just_pressed] => inputs.push(Controls::from_bits(
+                    pressed.parse().map_err(|_| invalid())?,
+                    just_pressed.parse().map_err(|_| invalid())?,
+                )),
+                [] => {},
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(Replay { config, inputs, checksums, tick: 0 })
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.inputs.len() as u32
+    }
+}
+
+#[derive(Resource)]
+struct ReplayRecorder {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    tick: u32,
+}
+
+impl ReplayRecorder {
+    fn new(path: PathBuf) -> Self {
+        ReplayRecorder { path, writer: None, tick: 0 }
+    }
+
+    fn write(&mut self, line: &str) {
+        if let Some(writer) = &mut self.writer {
+            if let Err(err) = writeln!(writer, "{}", line) {
+                println!("Replay recording stopped, cannot write {}: {}", self.path.display(), err);
+                self.writer = None;
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(writer) = &mut self.writer {
+            if let Err(err) = writer.flush() {
+                println!("Replay recording stopped, cannot write {}: {}", self.path.display(), err);
+                self.writer = None;
+            }
+        }
+    }
+}
+
+// Order independent hash of everything a tick can change, compared against the replay file
+fn world_checksum(
+    player_query: &Query<&Player>,
+    ennemy_query: &Query<&Ennemy>,
+    sanctuary_query: &Query<&Sanctuary>,
+) -> u64 {
+    let mut checksum: u64 = 0;
+    for player in player_query.iter() {
+        let (x, y) = player.get_pos();
+        checksum = checksum.wrapping_add(fnv1a(&[x.to_bits(), y.to_bits(), player.health() as u32]));
+    }
+    for ennemy in ennemy_query.iter() {
+        let (x, y) = ennemy.get_pos();
+        checksum = checksum.wrapping_add(fnv1a(&[x.to_bits(), y.to_bits(), ennemy.health() as u32]));
+    }
+    for sanctuary in sanctuary_query.iter() {
+        let (x, y) = sanctuary.get_pos();
+        checksum = checksum.wrapping_add(fnv1a(&[x.to_bits(), y.to_bits(), sanctuary.is_visible() as u32, sanctuary.is_unlocked() as u32]));
+    }
+    checksum
+}
+
+fn fnv1a(words: &[u32]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for word in words {
+        for byte in word.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+fn start_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    game_config: Res<GameConfig>,
+    pending_load: Option<Res<PendingLoad>>,
+) {
+    recorder.tick = 0;
+    // A replay starts from a fresh world, it cannot start from a continued game
+    if pending_load.is_some() {
+        println!("Replay recording disabled for a continued game");
+        recorder.writer = None;
+        return;
+    }
+    recorder.writer = match File::create(&recorder.path) {
+        Ok(file) => Some(BufWriter::new(file)),
+        Err(err) => {
+            println!("Replay recording disabled, cannot create {}: {}", recorder.path.display(), err);
+            None
+        }
+    };
+    let config = match ron::to_string(&*game_config) {
+        Ok(config) => config,
+        Err(err) => {
+            println!("Replay recording disabled, cannot serialize the config: {}", err);
+            recorder.writer = None;
+            return;
+        }
+    };
+    recorder.write(REPLAY_HEADER);
+    recorder.write(&format!("config {}", config));
+    recorder.flush();
+}
+
+fn record_tick(
+    mut recorder: ResMut<ReplayRecorder>,
+    controls: Res<Controls>,
+    player_query: Query<&Player>,
+    ennemy_query: Query<&Ennemy>,
+    sanctuary_query: Query<&Sanctuary>,
+) {
+    let (pressed, just_pressed) = controls.bits();
+    recorder.write(&format!("{} {}", pressed, just_pressed));
+    recorder.tick += 1;
+
+    if recorder.tick % REPLAY_CHECKSUM_INTERVAL == 0 {
+        let checksum = world_checksum(&player_query, &ennemy_query, &sanctuary_query);
+        let tick = recorder.tick;
+        recorder.write(&format!("checksum {} {:016x}", tick, checksum));
+        recorder.flush();
+    }
+}
+
+fn start_replay(
+    replay: Res<Replay>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    println!("Replaying {} ticks with seed {}", replay.ticks(), replay.config().seed);
+    state.set(GameState::Loading);
+}
+
+fn play_tick(
+    replay: Res<Replay>,
+    mut controls: ResMut<Controls>,
+    mut exit: EventWriter<AppExit>,
+) {
+    match replay.inputs.get(replay.tick as usize) {
+        Some(recorded) => *controls = *recorded,
+        None => {
+            println!("Replay finished after {} ticks, every checksum matched", replay.tick);
+            *controls = Controls::default();
+            exit.send(AppExit);
+        }
+    }
+}
+
+fn verify_tick(
+    mut replay: ResMut<Replay>,
+    player_query: Query<&Player>,
+    ennemy_query: Query<&Ennemy>,
+    sanctuary_query: Query<&Sanctuary>,
+) {
+    if replay.tick as usize >= replay.inputs.len() {
+        return;
+    }
+    replay.tick += 1;
+
+    let tick = replay.tick;
+    if let Some((_, expected)) = replay.checksums.iter().find(|(checksum_tick, _)| *checksum_tick == tick) {
+        let checksum = world_checksum(&player_query, &ennemy_query, &sanctuary_query);
+        if checksum != *expected {
+            panic!("Replay drifted at tick {}: expected checksum {:016x}, got {:016x}", tick, expected, checksum);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test writes its own file, they run in parallel
+    fn load(name: &str, content: &str) -> Result<Replay, String> {
+        let path = std::env::temp_dir().join(format!("zelda-replay-test-{}-{}.replay", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        let replay = Replay::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        replay
+    }
+
+    fn header() -> String {
+        format!("{}\nconfig {}\n", REPLAY_HEADER, ron::to_string(&GameConfig { seed: 42, ..GameConfig::default() }).unwrap())
+    }
+
+    #[test]
+    fn reads_config_inputs_and_checksums() {
+        let replay = load("valid", &format!("{}1 1\n\n0 0\nchecksum 2 00000000000000ff\n3 2\n", header())).unwrap();
+        assert_eq!(replay.config().seed, 42);
+        assert_eq!(replay.ticks(), 3);
+        assert!(replay.inputs[0] == Controls::from_bits(1, 1));
+        assert!(replay.inputs[2] == Controls::from_bits(3, 2));
+        assert_eq!(replay.checksums, vec![(2, 0xff)]);
+    }
+
+    #[test]
+    fn rejects_another_header() {
+        let err = load("header", "zelda-replay 1\nseed 42\n").err().expect("the replay should be rejected");
+        assert!(err.contains("is not a replay file"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_a_missing_or_invalid_config() {
+        let err = load("missing-config", &format!("{}\n0 0\n", REPLAY_HEADER)).err().expect("the replay should be rejected");
+        assert!(err.contains(":2: missing config"), "{}", err);
+        let err = load("invalid-config", &format!("{}\nconfig (map_size: 10.0)\n", REPLAY_HEADER)).err().expect("the replay should be rejected");
+        assert!(err.contains(":2: invalid config"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_malformed_lines_with_their_number() {
+        for (name, line) in [("fields", "1 2 3"), ("bits", "1 x"), ("overflow", "70000 0"), ("checksum", "checksum 2 zz"), ("tick", "checksum -1 ff")] {
+            let err = load(name, &format!("{}0 0\n{}\n", header(), line)).err().expect("the replay should be rejected");
+            assert!(err.contains(&format!(":4: invalid line \"{}\"", line)), "{}", err);
+        }
+    }
+}
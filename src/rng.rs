@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+use crate::config::GameConfig;
+use crate::GameState;
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, reseed_game_rng)
+            .add_systems(OnEnter(GameState::Loading), reseed_game_rng);
+    }
+}
+
+// Every gameplay random draw goes through this resource, so a seed always replays the same game
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    streams: HashMap<u64, StdRng>,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng { seed, streams: HashMap::new() }
+    }
+
+    // Persistent stream of a system, advanced on each draw
+    pub fn stream(&mut self, offset: u64) -> &mut StdRng {
+        let seed = self.seed;
+        self.streams.entry(offset).or_insert_with(|| StdRng::seed_from_u64(stream_seed(seed, offset, 0)))
+    }
+
+    // Fresh stream for the `index`-th entity generated under `offset`
+    pub fn sub_stream(&self, offset: u64, index: u64) -> StdRng {
+        StdRng::seed_from_u64(stream_seed(self.seed, offset, index))
+    }
+}
+
+// Each part goes through SplitMix64 in turn, so however many entities an offset generates
+// its streams never run into the ones of another offset
+fn stream_seed(seed: u64, offset: u64, index: u64) -> u64 {
+    splitmix64(splitmix64(splitmix64(seed) ^ offset) ^ index)
+}
+
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn reseed_game_rng(
+    mut commands: Commands,
+    game_config: Res<GameConfig>,
+) {
+    commands.insert_resource(GameRng::new(game_config.seed));
+}
@@ -0,0 +1,197 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::collisions::*;
+use crate::constants::*;
+use crate::ennemies;
+use crate::ennemies::{Ennemy, EnnemyState};
+use crate::entitypattern::*;
+use crate::player::{Player, PlayerState};
+use crate::rng::GameRng;
+use crate::structures::{Sanctuary, Tower};
+use crate::archetypes::EnnemyArchetypes;
+use crate::config::GameConfig;
+use crate::atlases::AtlasRegistry;
+use crate::spawners::SpawnDirector;
+use crate::GameState;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Playing), (apply_save, respawn_saved_ennemies).chain().run_if(resource_exists::<PendingLoad>()));
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub x: f32,
+    pub y: f32,
+    pub health: i32,
+    pub state: PlayerState,
+    pub facing_direction: Option<FacingDirection>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TowerSave {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SanctuarySave {
+    pub x: f32,
+    pub y: f32,
+    pub visibility: bool,
+    pub unlocked: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EnnemySave {
+    pub id: u32,
+    pub x: f32,
+    pub y: f32,
+    pub health: i32,
+    pub state: EnnemyState,
+    pub facing_direction: Option<FacingDirection>,
+    pub archetype: String,
+    pub spawner: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WavesSave {
+    pub elapsed: f32,
+    pub next_wave: usize,
+    pub wave_spawned: u32,
+    pub next_ennemy_id: u32,
+}
+
+// Only the version, read first so an old save is reported as such instead of as a parse error
+#[derive(Deserialize)]
+struct SaveVersion {
+    version: u32,
+}
+
+// A game in progress: its config rebuilds the map, the rest overwrites what it spawned
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    version: u32,
+    config: GameConfig,
+    player: PlayerSave,
+    tower: TowerSave,
+    sanctuaries: Vec<SanctuarySave>,
+    ennemies: Vec<EnnemySave>,
+    waves: WavesSave,
+}
+
+impl SaveGame {
+    pub fn capture(
+        game_config: &GameConfig,
+        player_query: &Query<&Player>,
+        tower_query: &Query<&Tower>,
+        sanctuary_query: &Query<&Sanctuary>,
+        ennemy_query: &Query<&Ennemy>,
+        spawn_director: &SpawnDirector,
+    ) -> Result<SaveGame, String> {
+        let player = player_query.get_single().map_err(|_| "no player to save".to_string())?;
+        let tower = tower_query.get_single().map_err(|_| "no tower to save".to_string())?;
+        Ok(SaveGame {
+            version: SAVE_VERSION,
+            config: game_config.clone(),
+            player: player.to_save(),
+            tower: tower.to_save(),
+            sanctuaries: sanctuary_query.iter().map(|sanctuary| sanctuary.to_save()).collect(),
+            ennemies: ennemy_query.iter().map(|ennemy| ennemy.to_save()).collect(),
+            waves: spawn_director.to_save(),
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| format!("cannot serialize save: {}", err))?;
+        std::fs::write(path, content)
+            .map_err(|err| format!("cannot write save {}: {}", path.display(), err))
+    }
+
+    pub fn load(path: &Path) -> Result<SaveGame, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read save {}: {}", path.display(), err))?;
+
+        let version = ron::from_str::<SaveVersion>(&content)
+            .map_err(|err| format!("{} is not a save file: {}", path.display(), err))?
+            .version;
+        if version != SAVE_VERSION {
+            return Err(format!("{} has save version {}, expected {}", path.display(), version, SAVE_VERSION));
+        }
+
+        let save: SaveGame = ron::from_str(&content).map_err(|err| format!("invalid save {}: {}", path.display(), err))?;
+        save.config.validate().map_err(|err| format!("invalid save {}: {}", path.display(), err))?;
+        Ok(save)
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+}
+
+// Save being continued, applied once the world of its config has been spawned
+#[derive(Resource)]
+pub struct PendingLoad(pub SaveGame);
+
+fn apply_save(
+    pending: Res<PendingLoad>,
+    mut player_query: Query<(&mut Player, &mut PreviousPosition)>,
+    mut tower_query: Query<(&mut Tower, &mut CollisionComponent), Without<Sanctuary>>,
+    mut sanctuary_query: Query<(&mut Sanctuary, &mut CollisionComponent), Without<Tower>>,
+    mut spawn_director: ResMut<SpawnDirector>,
+) {
+    let save = &pending.0;
+    // Spawners come back with the map, only the wave script picks up where it was
+    spawn_director.restore(&save.waves);
+
+    for (mut player, mut previous) in player_query.iter_mut() {
+        player.restore(&save.player);
+        *previous = PreviousPosition::new(save.player.x, save.player.y);
+    }
+
+    for (mut tower, mut collision_component) in tower_query.iter_mut() {
+        tower.restore(&save.tower);
+        collision_component.update_hitbox(&*tower);
+    }
+
+    // Sanctuaries come back at the same place from the config, so they are matched by position
+    for (mut sanctuary, mut collision_component) in sanctuary_query.iter_mut() {
+        if let Some(saved) = save.sanctuaries.iter().find(|saved| (saved.x, saved.y) == sanctuary.get_pos()) {
+            sanctuary.restore(saved);
+            collision_component.update_hitbox(&*sanctuary);
+        }
+    }
+}
+
+// Ennemies killed before the save are gone, the others come back with their own AI stream
+fn respawn_saved_ennemies(
+    mut commands: Commands,
+    pending: Res<PendingLoad>,
+    atlases: Res<AtlasRegistry>,
+    game_rng: Res<GameRng>,
+    ennemy_archetypes: Res<EnnemyArchetypes>,
+    ennemy_query: Query<Entity, With<Ennemy>>,
+) {
+    let save = &pending.0;
+
+    for entity in ennemy_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for saved in save.ennemies.iter() {
+        let Some(archetype) = ennemy_archetypes.get(&saved.archetype) else {
+            println!("Saved ennemy {} is a {}, which is not in {} anymore", saved.id, saved.archetype, ENNEMY_ARCHETYPES_PATH);
+            continue;
+        };
+        let ennemy = Ennemy::from_save(saved, archetype, game_rng.sub_stream(OFFSET_ENNEMY_AI, saved.id as u64));
+        ennemies::spawn_ennemy(&mut commands, &atlases, ennemy);
+    }
+
+    println!("Save loaded: player health {}, {} ennemies left", save.player.health, save.ennemies.len());
+    commands.remove_resource::<PendingLoad>();
+}
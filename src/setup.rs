@@ -1,24 +1,16 @@
 use bevy::prelude::*;
 use rand::prelude::*;
-use crate::{constants::*, player::*, collisions::{CollisionComponent, Collisionable}, GameState, GameConfig};
+use crate::atlases::AtlasRegistry;
+use crate::{constants::*, collisions::{CollisionComponent, CollisionFilter, Hitbox}, headless::*, rng::GameRng, config::GameConfig, spatial::SpatialIndex, triggers::TriggerVolume, GameState, SpawnSet};
 
 
 pub struct SetupPlugin;
 
 impl Plugin for SetupPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnExit(GameState::Menu), (setup, 
-                                                    setup_random_trees, 
-                                                    setup_random_bushes, 
-                                                    setup_random_graves, ))
-            .add_systems(OnExit(GameState::Loading), (setup, 
-                                                        setup_random_trees, 
-                                                        setup_random_bushes, 
-                                                        setup_random_graves, ))
-            .add_systems(Update, (
-                // zoom_camera, 
-                                                   track_player,
-                                                   ).run_if(in_state(GameState::Playing)));
+        app.add_systems(OnExit(GameState::Loading), (setup.run_if(is_rendering), 
+                                                        (setup_random_trees, setup_random_graves).chain().in_set(SpawnSet::Decor),
+                                                        setup_random_bushes, ));
     }
 }
 
@@ -30,7 +22,7 @@ pub fn setup(
     asset_server: Res<AssetServer>,
 ) {
     commands.spawn(SpriteBundle {
-        texture: asset_server.load("Background/background.png"),
+        texture: asset_server.load(BACKGROUND_IMAGE),
         transform: Transform {
             translation: Vec3::new(0., 0., Z_LAYER_BACKGROUND),
             ..Transform::default()
@@ -40,51 +32,6 @@ pub fn setup(
 
 }
 
-// pub fn zoom_camera(
-//     mut query: Query<&mut OrthographicProjection>,
-//     keyboard_input: Res<Input<KeyCode>>,
-// ) {
-//     let mut transform = query.single_mut();
-//     if keyboard_input.pressed(KeyCode::S) && transform.scale < CAMERA_MAX_SCALE {
-//         transform.scale = transform.scale + 0.01;
-//     }
-//     if keyboard_input.pressed(KeyCode::Z) && transform.scale > CAMERA_MIN_SCALE{
-//         transform.scale = transform.scale - 0.01;
-//     }
-//     if keyboard_input.pressed(KeyCode::R) {
-//         transform.scale = CAMERA_DEFAULT_SCALE;
-//     }
-//     if keyboard_input.pressed(KeyCode::M) {
-//         transform.scale = CAMERA_MAX_SCALE;
-//     }
-
-// }
-
-pub fn track_player(
-    player_query: Query<&Transform, With<Player>>,
-    mut camera: Query<(&mut Camera, &mut Transform), Without<Player>>,
-    mut camera_proj : Query<&mut OrthographicProjection, With<Camera>>,
-) {
-    let player_transform = player_query.single();
-    let mut camera_transform = camera.single_mut().1;
-    let camera_projection = camera_proj.single_mut();
-
-    let camera_range_height = camera_projection.scale * WINDOW_HEIGHT / 2.;
-    let camera_range_width = camera_projection.scale * WINDOW_WIDTH / 2.;
-    
-    let x = player_transform.translation.x;
-    let y = player_transform.translation.y;
-
-    let camera_max_x = MAP_SIZE / 2. - camera_range_width;
-    let camera_min_x = -MAP_SIZE / 2. + camera_range_width;
-    let camera_max_y = MAP_SIZE / 2. - camera_range_height;
-    let camera_min_y = -MAP_SIZE / 2. + camera_range_height;
-
-    camera_transform.translation.x = if x > camera_max_x { camera_max_x } else if x < camera_min_x { camera_min_x } else { x };
-    camera_transform.translation.y = if y > camera_max_y { camera_max_y } else if y < camera_min_y { camera_min_y } else { y };
-}
-
-
 pub enum BackgroundObjectType {
     Tree,
     Bush,
@@ -104,131 +51,112 @@ impl BackgroundObjects {
     }
 }
 
+// Hitbox of each sprite of Background/trees.png, only the trunk blocks
+const TREE_HITBOXES: [Hitbox; 3] = [
+    Hitbox::circle(4.).with_offset(0., -TREE_HEIGHT / 2. + 12.),
+    Hitbox::circle(3.5).with_offset(0., -TREE_HEIGHT / 2. + 12.),
+    Hitbox::circle(2.5).with_offset(0., -TREE_HEIGHT / 2. + 12.),
+];
+
+// Hitbox of each sprite of Background/graves.png: the tomb, the headstone and the bench
+const GRAVE_HITBOXES: [Hitbox; 3] = [
+    Hitbox::rect(32., 57.),
+    Hitbox::rect(30., 17.).with_offset(0., -9.),
+    Hitbox::capsule(56., 22.).with_offset(0., -10.),
+];
+
+// Foliage of Background/bushes.png, where the player can hide
+const BUSH_HITBOX: Hitbox = Hitbox::capsule(36., 24.).with_offset(0., -8.);
+
+// A spot of the map where the hitbox touches nothing solid yet, none when every try lands on something
+fn find_free_spot(rng: &mut StdRng, map_size: f32, hitbox: Hitbox, spatial_index: &SpatialIndex) -> Option<(f32, f32, CollisionComponent)> {
+    let limit = map_size / 2. - 32.;
+    (0..DECOR_PLACEMENT_ATTEMPTS)
+        .map(|_| {
+            let x = rng.gen_range(-limit..limit);
+            let y = rng.gen_range(-limit..limit);
+            (x, y, CollisionComponent::from_hitbox(x, y, hitbox, CollisionFilter::TERRAIN))
+        })
+        .find(|(_, _, collisioncomponent)| {
+            let (x_col, y_col, w, h) = collisioncomponent.get_hitbox();
+            spatial_index.colliders_near(x_col, y_col, w, h).is_empty()
+        })
+}
+
 pub fn setup_random_trees(
     mut commands: Commands, 
-    asset_server: Res<AssetServer>, 
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    collisionable_query: Query<&CollisionComponent>,
+    atlases: Res<AtlasRegistry>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    game_rng: Res<GameRng>,
     game_config: Res<GameConfig>,
 ) {
-    let tree_texture_handle = asset_server.load("Background/trees.png");
-    let tree_texture_atlas = TextureAtlas::from_grid(tree_texture_handle, Vec2::new(TREE_WIDTH, TREE_HEIGHT), 3, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(3., 0.)));
-    let tree_texture_atlas_handle = texture_atlases.add(tree_texture_atlas);
+    let map_size = game_config.map_size;
+    let tree_texture_atlas_handle = atlases.atlas(TREES_SHEET);
 
-    let mut rng = StdRng::seed_from_u64(game_config.seed + OFFSET_TREE);
+    let mut rng = game_rng.sub_stream(OFFSET_TREE, 0);
 
-    for _ in 0..TREE_NUMBER {
-        let mut x;
-        let mut y;
-        loop {
-            x = rng.gen_range(-MAP_SIZE / 2. + 32.0..MAP_SIZE / 2. - 32.);
-            y = rng.gen_range(-MAP_SIZE / 2. + 32.0..MAP_SIZE / 2. - 32.);
-            let collisioncomponent = CollisionComponent::new(x, y- TREE_HEIGHT/2. + 12., 5., 5.);
-            if !collisionable_query.iter().any(|collisionable| collisioncomponent.would_collide_with(collisionable)) {
-                break;
-            }
-        }
-        let index = rng.gen_range(0..3);
-
-        
-        let collisioncomponent = CollisionComponent::new(x, y- TREE_HEIGHT/2. + 12., 5., 5.);
+    for _ in 0..game_config.tree_number {
+        let index = rng.gen_range(0..TREE_HITBOXES.len());
+        let Some((x, y, collisioncomponent)) = find_free_spot(&mut rng, map_size, TREE_HITBOXES[index], &spatial_index) else {
+            println!("No free spot left for a tree after {} tries, skipped", DECOR_PLACEMENT_ATTEMPTS);
+            continue;
+        };
 
-        commands.spawn(SpriteSheetBundle {
-            texture_atlas: tree_texture_atlas_handle.clone(),
-            transform: Transform {
-                translation: Vec3::new(x, y, -y+MAP_SIZE/2. + TREE_HEIGHT/2. - 12.),
-                ..Transform::default()
-            },
-            sprite: TextureAtlasSprite::new(index),
-            ..Default::default()
+        let tree = spawn_sprite_sheet(&mut commands, &tree_texture_atlas_handle, TextureAtlasSprite::new(index), Transform {
+            translation: Vec3::new(x, y, -y+map_size/2. + TREE_HEIGHT/2. - 12.),
+            ..Transform::default()
         }).insert(BackgroundObjects { obj_type: BackgroundObjectType::Tree })
-        .insert(collisioncomponent);
+        .insert(collisioncomponent.clone())
+        .id();
+        // Indexed right away so the next trees and the graves keep clear of it
+        spatial_index.insert(tree, &collisioncomponent);
     }
 }
 
 pub fn setup_random_bushes(
     mut commands: Commands, 
-    asset_server: Res<AssetServer>, 
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    atlases: Res<AtlasRegistry>,
+    game_rng: Res<GameRng>,
     game_config: Res<GameConfig>,
 ) {
-    let bush_texture_handle = asset_server.load("Background/bushes.png");
-    let bush_texture_atlas = TextureAtlas::from_grid(bush_texture_handle, Vec2::new(BUSH_WIDTH, BUSH_HEIGHT), 3, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-    let bush_texture_atlas_handle = texture_atlases.add(bush_texture_atlas);
+    let map_size = game_config.map_size;
+    let bush_texture_atlas_handle = atlases.atlas(BUSHES_SHEET);
 
-    let mut rng = StdRng::seed_from_u64(game_config.seed + OFFSET_BUSH );
-    for _ in 0..BUSH_NUMBER {
-        let x = rng.gen_range(-MAP_SIZE / 2. + 32.0..MAP_SIZE / 2. - 32.);
-        let y = rng.gen_range(-MAP_SIZE / 2. + 32.0..MAP_SIZE / 2. - 32.);
+    let mut rng = game_rng.sub_stream(OFFSET_BUSH, 0);
+    for _ in 0..game_config.bush_number {
+        let x = rng.gen_range(-map_size / 2. + 32.0..map_size / 2. - 32.);
+        let y = rng.gen_range(-map_size / 2. + 32.0..map_size / 2. - 32.);
         let index = rng.gen_range(0..3);
-        commands.spawn(SpriteSheetBundle {
-            texture_atlas: bush_texture_atlas_handle.clone(),
-            transform: Transform {
-                translation: Vec3::new(x, y, -y+MAP_SIZE/2.),
-                ..Transform::default()
-            },
-            sprite: TextureAtlasSprite::new(index),
-            ..Default::default()
+        spawn_sprite_sheet(&mut commands, &bush_texture_atlas_handle, TextureAtlasSprite::new(index), Transform {
+            translation: Vec3::new(x, y, -y+map_size/2.),
+            ..Transform::default()
         })
-        .insert(BackgroundObjects { obj_type: BackgroundObjectType::Bush });
+        .insert(BackgroundObjects { obj_type: BackgroundObjectType::Bush })
+        .insert((TriggerVolume::new(CollisionFilter::BUSH), CollisionComponent::from_hitbox(x, y, BUSH_HITBOX, CollisionFilter::BUSH)));
     }
 }
 
 pub fn setup_random_graves(
     mut commands: Commands, 
-    asset_server: Res<AssetServer>, 
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    collisionable_query: Query<&CollisionComponent>,
+    atlases: Res<AtlasRegistry>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    game_rng: Res<GameRng>,
     game_config: Res<GameConfig>,
 ) {
-    let big_grave_texture_handle = asset_server.load("Background/graves.png");
-    let big_grave_texture_atlas = TextureAtlas::from_grid(big_grave_texture_handle, Vec2::new(64., 64.), 3, 1, Some(Vec2::new(0., 0.)), Some(Vec2::new(0., 0.)));
-    let big_grave_texture_atlas_handle = texture_atlases.add(big_grave_texture_atlas);
-
-    let mut rng = StdRng::seed_from_u64(game_config.seed + OFFSET_GRAVE);
-    for _ in 0..GRAVES_NUMBER {
-        let mut x = rng.gen_range(-MAP_SIZE / 2. + 32.0..MAP_SIZE / 2. - 32.);
-        let mut y = rng.gen_range(-MAP_SIZE / 2. + 32.0..MAP_SIZE / 2. - 32.);
-        let mut collisioncomponent: CollisionComponent;
-        let index = rng.gen_range(0..3);
-        loop {
-            let y_col = match index {
-                0 => y,
-                1 => y - 9.,
-                2 => y - 10.,
-                _ => y - 16.,
-            };
-
-            let w = match index {
-                0 => 32.,
-                1 => 30.,
-                2 => 56.,
-                _ => 32.,
-            };
-
-            let h = match index {
-                0 => 57.,
-                1 => 17.,
-                2 => 22.,
-                _ => 40.,
-            };
-
-            collisioncomponent = CollisionComponent::new(x , y_col, w, h);
-            if !collisionable_query.iter().any(|collisionable| collisioncomponent.would_collide_with(collisionable)) {
-                break;
-            }
-            x = rng.gen_range(-MAP_SIZE / 2. + 32.0..MAP_SIZE / 2. - 32.);
-            y = rng.gen_range(-MAP_SIZE / 2. + 32.0..MAP_SIZE / 2. - 32.);
-        }
-
-        commands.spawn(SpriteSheetBundle {
-            texture_atlas: big_grave_texture_atlas_handle.clone(),
-            transform: Transform {
-                translation: Vec3::new(x, y, -y+MAP_SIZE/2.),
-                ..Transform::default()
-            },
-            sprite: TextureAtlasSprite::new(index),
-            ..Default::default()
+    let map_size = game_config.map_size;
+    let big_grave_texture_atlas_handle = atlases.atlas(GRAVES_SHEET);
+
+    let mut rng = game_rng.sub_stream(OFFSET_GRAVE, 0);
+    for _ in 0..game_config.graves_number {
+        let index = rng.gen_range(0..GRAVE_HITBOXES.len());
+        let Some((x, y, collisioncomponent)) = find_free_spot(&mut rng, map_size, GRAVE_HITBOXES[index], &spatial_index) else {
+            println!("No free spot left for a grave after {} tries, skipped", DECOR_PLACEMENT_ATTEMPTS);
+            continue;
+        };
+
+        let grave = spawn_sprite_sheet(&mut commands, &big_grave_texture_atlas_handle, TextureAtlasSprite::new(index), Transform {
+            translation: Vec3::new(x, y, -y+map_size/2.),
+            ..Transform::default()
         })
         .insert(BackgroundObjects { obj_type: match index {
             0 => BackgroundObjectType::BigGrave,
@@ -236,7 +164,9 @@ pub fn setup_random_graves(
             2 => BackgroundObjectType::Bench,
             _ => BackgroundObjectType::BigGrave,
         } })
-        .insert(collisioncomponent);
+        .insert(collisioncomponent.clone())
+        .id();
+        spatial_index.insert(grave, &collisioncomponent);
     }
 }
 
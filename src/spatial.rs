@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use crate::collisions::{are_overlapping, Collider, CollisionComponent};
+use crate::constants::*;
+use crate::TickSet;
+
+pub struct SpatialIndexPlugin;
+
+impl Plugin for SpatialIndexPlugin {
+    fn build(&self, app: &mut App) {
+        // Synced before each gameplay step reads it, and every frame so no despawn is missed between two ticks
+        app.init_resource::<SpatialIndex>()
+            .add_systems(FixedUpdate, (sync_spatial_index.before(TickSet::Player),
+                                                    sync_spatial_index.after(TickSet::Player).before(TickSet::Ennemies),
+                                                    sync_spatial_index.after(TickSet::Ennemies).before(TickSet::Projectiles),
+                                                ))
+            .add_systems(Last, sync_spatial_index);
+    }
+}
+
+type Cell = (i32, i32);
+
+#[derive(Clone, Copy, PartialEq)]
+struct CellRange {
+    min: Cell,
+    max: Cell,
+}
+
+impl CellRange {
+    fn cells(self) -> impl Iterator<Item = Cell> {
+        (self.min.0..=self.max.0).flat_map(move |x| (self.min.1..=self.max.1).map(move |y| (x, y)))
+    }
+}
+
+// Uniform grid over every CollisionComponent: a query only looks at the boxes of the cells it covers.
+// Results are sorted by entity so they do not depend on the hashing and replays stay deterministic
+#[derive(Resource)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<Entity>>,
+    boxes: HashMap<Entity, (CollisionComponent, CellRange)>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        SpatialIndex::new(SPATIAL_CELL_SIZE)
+    }
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialIndex {
+            cell_size,
+            cells: HashMap::new(),
+            boxes: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, x: f32, y: f32) -> Cell {
+        ((x / self.cell_size).floor() as i32, (y / self.cell_size).floor() as i32)
+    }
+
+    fn cell_range(&self, x: f32, y: f32, w: f32, h: f32) -> CellRange {
+        CellRange {
+            min: self.cell(x - w / 2., y - h / 2.),
+            max: self.cell(x + w / 2., y + h / 2.),
+        }
+    }
+
+    // Adds the entity or moves it to its new box, the cells are only touched when the box changes of cells
+    pub fn insert(&mut self, entity: Entity, collision: &CollisionComponent) {
+        let (x, y, w, h) = collision.get_hitbox();
+        let range = self.cell_range(x, y, w, h);
+        match self.boxes.insert(entity, (collision.clone(), range)) {
+            Some((_, old_range)) if old_range == range => return,
+            Some((_, old_range)) => self.unlink(entity, old_range),
+            None => {}
+        }
+        for cell in range.cells() {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some((_, range)) = self.boxes.remove(&entity) {
+            self.unlink(entity, range);
+        }
+    }
+
+    fn unlink(&mut self, entity: Entity, range: CellRange) {
+        for cell in range.cells() {
+            if let Some(entities) = self.cells.get_mut(&cell) {
+                entities.retain(|other| *other != entity);
+                if entities.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    fn entities_in(&self, cell: Cell) -> impl Iterator<Item = Entity> + '_ {
+        self.cells.get(&cell).into_iter().flatten().copied()
+    }
+
+    // Entities whose box overlaps the given one, edges touching included like are_overlapping
+    pub fn colliders_near(&self, x: f32, y: f32, w: f32, h: f32) -> Vec<Entity> {
+        let mut found: Vec<Entity> = self.cell_range(x, y, w, h).cells()
+            .flat_map(|cell| self.entities_in(cell))
+            .filter(|entity| {
+                let (x2, y2, w2, h2) = self.boxes[entity].0.get_hitbox();
+                are_overlapping(Collider::rect(x, y, w, h), Collider::rect(x2, y2, w2, h2))
+            })
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    // Closest accepted box within max_distance of the point, with its distance (0 when the point is inside)
+    pub fn nearest(&self, x: f32, y: f32, max_distance: f32, accept: impl Fn(Entity) -> bool) -> Option<(Entity, f32)> {
+        let center = self.cell(x, y);
+        let max_ring = (max_distance / self.cell_size).ceil() as i32 + 1;
+        let mut best: Option<(Entity, f32)> = None;
+
+        for ring in 0..=max_ring {
+            // The boxes of this ring are at least (ring - 1) cells away, they cannot beat what was found
+            if best.is_some_and(|(_, distance)| distance <= (ring - 1) as f32 * self.cell_size) {
+                break;
+            }
+            for entity in ring_cells(center, ring).flat_map(|cell| self.entities_in(cell)) {
+                if !accept(entity) {
+                    continue;
+                }
+                let distance = distance_to_box(x, y, &self.boxes[&entity].0);
+                if distance <= max_distance && is_better(entity, distance, best) {
+                    best = Some((entity, distance));
+                }
+            }
+        }
+        best
+    }
+
+    // First accepted box hit by the ray, with the distance along it. Walks the grid cell by cell
+    // and stops as soon as a hit is closer than the border of the current cell
+    pub fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32, accept: impl Fn(Entity) -> bool) -> Option<(Entity, f32)> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return None;
+        }
+
+        let (mut cell_x, mut cell_y) = self.cell(origin.x, origin.y);
+        let step_x = if direction.x > 0. { 1 } else if direction.x < 0. { -1 } else { 0 };
+        let step_y = if direction.y > 0. { 1 } else if direction.y < 0. { -1 } else { 0 };
+        let border = |cell: i32, step: i32| (cell + (step > 0) as i32) as f32 * self.cell_size;
+
+        // Distance along the ray to the next vertical and horizontal cell borders
+        let (mut t_max_x, t_delta_x) = if step_x != 0 {
+            ((border(cell_x, step_x) - origin.x) / direction.x, self.cell_size / direction.x.abs())
+        } else {
+            (f32::INFINITY, f32::INFINITY)
+        };
+        let (mut t_max_y, t_delta_y) = if step_y != 0 {
+            ((border(cell_y, step_y) - origin.y) / direction.y, self.cell_size / direction.y.abs())
+        } else {
+            (f32::INFINITY, f32::INFINITY)
+        };
+
+        let mut best: Option<(Entity, f32)> = None;
+        loop {
+            for entity in self.entities_in((cell_x, cell_y)) {
+                if !accept(entity) {
+                    continue;
+                }
+                if let Some(distance) = ray_hit(origin, direction, &self.boxes[&entity].0) {
+                    if distance <= max_distance && is_better(entity, distance, best) {
+                        best = Some((entity, distance));
+                    }
+                }
+            }
+
+            let cell_exit = t_max_x.min(t_max_y);
+            if best.is_some_and(|(_, distance)| distance <= cell_exit) || cell_exit > max_distance {
+                return best;
+            }
+            if t_max_x < t_max_y {
+                cell_x += step_x;
+                t_max_x += t_delta_x;
+            } else {
+                cell_y += step_y;
+                t_max_y += t_delta_y;
+            }
+        }
+    }
+}
+
+// Ties go to the oldest entity so the answer does not depend on the order of the cells
+fn is_better(entity: Entity, distance: f32, best: Option<(Entity, f32)>) -> bool {
+    match best {
+        None => true,
+        Some((best_entity, best_distance)) => distance < best_distance || (distance == best_distance && entity < best_entity),
+    }
+}
+
+fn ring_cells(center: Cell, ring: i32) -> impl Iterator<Item = Cell> {
+    (-ring..=ring).flat_map(move |dx| (-ring..=ring).map(move |dy| (dx, dy)))
+        .filter(move |(dx, dy)| dx.abs() == ring || dy.abs() == ring)
+        .map(move |(dx, dy)| (center.0 + dx, center.1 + dy))
+}
+
+fn distance_to_box(x: f32, y: f32, collision: &CollisionComponent) -> f32 {
+    let (box_x, box_y, w, h) = collision.get_hitbox();
+    let dx = ((x - box_x).abs() - w / 2.).max(0.);
+    let dy = ((y - box_y).abs() - h / 2.).max(0.);
+    (dx * dx + dy * dy).sqrt()
+}
+
+// Slab test, a ray starting inside the box hits it at 0
+fn ray_hit(origin: Vec2, direction: Vec2, collision: &CollisionComponent) -> Option<f32> {
+    let (x, y, w, h) = collision.get_hitbox();
+    let half_size = Vec2::new(w, h) / 2.;
+    let t1 = (Vec2::new(x, y) - half_size - origin) / direction;
+    let t2 = (Vec2::new(x, y) + half_size - origin) / direction;
+    let t_near = t1.min(t2).max_element().max(0.);
+    let t_far = t1.max(t2).min_element();
+    (t_far >= t_near).then_some(t_near)
+}
+
+fn sync_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    changed: Query<(Entity, &CollisionComponent), Changed<CollisionComponent>>,
+    mut removed: RemovedComponents<CollisionComponent>,
+) {
+    for entity in removed.iter() {
+        index.remove(entity);
+    }
+    for (entity, collision) in changed.iter() {
+        index.insert(entity, collision);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collisions::CollisionFilter;
+
+    fn index_with(boxes: &[(u32, f32, f32, f32, f32)]) -> SpatialIndex {
+        let mut index = SpatialIndex::new(64.);
+        for &(id, x, y, w, h) in boxes {
+            index.insert(Entity::from_raw(id), &CollisionComponent::new(x, y, w, h, CollisionFilter::TERRAIN));
+        }
+        index
+    }
+
+    fn entity(id: u32) -> Entity {
+        Entity::from_raw(id)
+    }
+
+    #[test]
+    fn colliders_near_includes_touching_edges_once_and_sorted() {
+        // The second box spans four cells, it is still reported once
+        let index = index_with(&[(3, 0., 0., 10., 10.), (1, 64., 64., 100., 100.), (2, 300., 300., 10., 10.)]);
+        assert_eq!(index.colliders_near(10., 0., 10., 10.), vec![entity(3)]);
+        assert_eq!(index.colliders_near(20., 20., 40., 40.), vec![entity(1), entity(3)]);
+        assert!(index.colliders_near(-100., -100., 10., 10.).is_empty());
+    }
+
+    #[test]
+    fn moved_and_removed_boxes_leave_their_cells() {
+        let mut index = index_with(&[(1, 0., 0., 10., 10.)]);
+        index.insert(entity(1), &CollisionComponent::new(200., 0., 10., 10., CollisionFilter::TERRAIN));
+        assert!(index.colliders_near(0., 0., 10., 10.).is_empty());
+        assert_eq!(index.colliders_near(200., 0., 10., 10.), vec![entity(1)]);
+        index.remove(entity(1));
+        assert!(index.colliders_near(200., 0., 10., 10.).is_empty());
+    }
+
+    #[test]
+    fn raycast_with_zero_direction_hits_nothing() {
+        let index = index_with(&[(1, 0., 0., 10., 10.)]);
+        assert_eq!(index.raycast(Vec2::ZERO, Vec2::ZERO, 100., |_| true), None);
+    }
+
+    #[test]
+    fn raycast_starting_inside_a_box_hits_it_at_zero() {
+        let index = index_with(&[(1, 0., 0., 10., 10.)]);
+        assert_eq!(index.raycast(Vec2::ZERO, Vec2::X, 100., |_| true), Some((entity(1), 0.)));
+    }
+
+    #[test]
+    fn raycast_finds_the_first_box_across_cells() {
+        let index = index_with(&[(1, 300., 0., 10., 10.), (2, 150., 0., 10., 10.)]);
+        assert_eq!(index.raycast(Vec2::ZERO, Vec2::X, 1000., |_| true), Some((entity(2), 145.)));
+        assert_eq!(index.raycast(Vec2::ZERO, Vec2::X, 1000., |entity| entity != Entity::from_raw(2)), Some((entity(1), 295.)));
+        assert_eq!(index.raycast(Vec2::ZERO, Vec2::X, 100., |_| true), None);
+        assert_eq!(index.raycast(Vec2::ZERO, -Vec2::X, 1000., |_| true), None);
+    }
+
+    #[test]
+    fn raycast_along_a_cell_border() {
+        // The ray runs on y = 64, the border between two rows of cells, the box sits across it
+        let index = index_with(&[(1, 200., 64., 10., 10.)]);
+        assert_eq!(index.raycast(Vec2::new(0., 64.), Vec2::X, 1000., |_| true), Some((entity(1), 195.)));
+        assert_eq!(index.raycast(Vec2::new(200., 0.), Vec2::Y, 1000., |_| true), Some((entity(1), 59.)));
+    }
+
+    #[test]
+    fn raycast_ties_go_to_the_oldest_entity() {
+        let index = index_with(&[(5, 100., 10., 10., 30.), (2, 100., -10., 10., 30.)]);
+        assert_eq!(index.raycast(Vec2::ZERO, Vec2::X, 1000., |_| true), Some((entity(2), 95.)));
+    }
+
+    #[test]
+    fn nearest_is_zero_inside_a_box() {
+        let index = index_with(&[(1, 0., 0., 10., 10.), (2, 20., 0., 10., 10.)]);
+        assert_eq!(index.nearest(1., 1., 100., |_| true), Some((entity(1), 0.)));
+    }
+
+    #[test]
+    fn nearest_looks_past_the_first_rings() {
+        let index = index_with(&[(1, 400., 0., 10., 10.), (2, 0., 250., 10., 10.)]);
+        assert_eq!(index.nearest(0., 0., 1000., |_| true), Some((entity(2), 245.)));
+        assert_eq!(index.nearest(0., 0., 1000., |entity| entity != Entity::from_raw(2)), Some((entity(1), 395.)));
+        assert_eq!(index.nearest(0., 0., 200., |_| true), None);
+    }
+
+    #[test]
+    fn nearest_ties_go_to_the_oldest_entity() {
+        let index = index_with(&[(7, 100., 0., 10., 10.), (3, -100., 0., 10., 10.)]);
+        assert_eq!(index.nearest(0., 0., 1000., |_| true), Some((entity(3), 95.)));
+    }
+}
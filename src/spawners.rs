@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use rand::prelude::*;
+use crate::archetypes::EnnemyArchetypes;
+use crate::atlases::AtlasRegistry;
+use crate::collisions::*;
+use crate::config::GameConfig;
+use crate::constants::*;
+use crate::ennemies::{self, Ennemy};
+use crate::player::Player;
+use crate::rng::GameRng;
+use crate::save::WavesSave;
+use crate::setup::{BackgroundObjectType, BackgroundObjects};
+use crate::{GameState, TickSet};
+
+pub struct SpawnerPlugin;
+
+impl Plugin for SpawnerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::Loading), reset_spawn_director)
+            .add_systems(FixedUpdate, (place_spawners, run_spawners).chain().in_set(TickSet::Structures).run_if(in_state(GameState::Playing)));
+    }
+}
+
+// A spot ennemies keep coming out of, in front of a grave or anywhere on the map
+#[derive(Component)]
+pub struct Spawner {
+    id: u32,
+    position: Vec2,
+    cooldown_left: f32,
+}
+
+// Where the game is at in its wave script, and the id the next spawned ennemy gets so its random
+// streams only depend on the seed
+#[derive(Resource)]
+pub struct SpawnDirector {
+    placed: bool,
+    elapsed: f32,
+    next_wave: usize,
+    // Ennemies of the next wave already out, it only ends once all of them are
+    wave_spawned: u32,
+    next_ennemy_id: u32,
+}
+
+impl SpawnDirector {
+    // The ennemies summoned at the start take the ids before the first one
+    fn new(first_ennemy_id: u32) -> Self {
+        SpawnDirector { placed: false, elapsed: 0., next_wave: 0, wave_spawned: 0, next_ennemy_id: first_ennemy_id }
+    }
+
+    fn take_ennemy_id(&mut self) -> u32 {
+        self.next_ennemy_id += 1;
+        self.next_ennemy_id - 1
+    }
+
+    pub fn to_save(&self) -> WavesSave {
+        WavesSave {
+            elapsed: self.elapsed,
+            next_wave: self.next_wave,
+            wave_spawned: self.wave_spawned,
+            next_ennemy_id: self.next_ennemy_id,
+        }
+    }
+
+    pub fn restore(&mut self, save: &WavesSave) {
+        self.elapsed = save.elapsed;
+        self.next_wave = save.next_wave;
+        self.wave_spawned = save.wave_spawned;
+        self.next_ennemy_id = save.next_ennemy_id;
+    }
+}
+
+fn reset_spawn_director(
+    mut commands: Commands,
+    game_config: Res<GameConfig>,
+) {
+    commands.insert_resource(SpawnDirector::new(game_config.ennemies_number));
+}
+
+// Everything an ennemy needs to come out of a spawner
+#[derive(SystemParam)]
+struct EnnemyFactory<'w, 's> {
+    commands: Commands<'w, 's>,
+    atlases: Res<'w, AtlasRegistry>,
+    game_rng: Res<'w, GameRng>,
+    ennemy_archetypes: Res<'w, EnnemyArchetypes>,
+    surroundings: Surroundings<'w, 's>,
+}
+
+impl EnnemyFactory<'_, '_> {
+    // Nothing solid where the biggest ennemy would stand
+    fn is_free(&self, position: Vec2) -> bool {
+        let size = self.ennemy_archetypes.largest_hitbox();
+        let collider = Collider::new(position, Shape::Rect { w: size.x, h: size.y });
+        let filter = CollisionFilter::ENNEMY.ignoring(Layers::ENNEMY.with(Layers::PLAYER));
+        self.surroundings.first_hit(Entity::PLACEHOLDER, collider, filter, Vec2::ZERO).is_none()
+    }
+
+    // Of the given archetype or picked from the spawn table, somewhere within spread of the spawner
+    fn spawn(&mut self, id: u32, spawner: &Spawner, archetype: Option<&str>, spread: f32) {
+        let mut rng = self.game_rng.sub_stream(OFFSET_ENNEMY, id as u64);
+        let archetype = match archetype.and_then(|name| self.ennemy_archetypes.get(name)) {
+            Some(archetype) => archetype.clone(),
+            None => self.ennemy_archetypes.pick(&mut rng).clone(),
+        };
+        let offset = Vec2::new(rng.gen_range(-spread..=spread), rng.gen_range(-spread..=spread));
+        let position = if self.is_free(spawner.position + offset) { spawner.position + offset } else { spawner.position };
+
+        let ennemy = Ennemy::spawned(id, position.x, position.y, &archetype, self.game_rng.sub_stream(OFFSET_ENNEMY_AI, id as u64), spawner.id);
+        ennemies::spawn_ennemy(&mut self.commands, &self.atlases, ennemy);
+    }
+}
+
+// Once the map is there: in front of some of the graves, then at free spots of the map
+fn place_spawners(
+    mut director: ResMut<SpawnDirector>,
+    graves: Query<(&BackgroundObjects, &CollisionComponent)>,
+    mut factory: EnnemyFactory,
+    game_config: Res<GameConfig>,
+) {
+    if director.placed {
+        return;
+    }
+    director.placed = true;
+
+    let config = &game_config.spawners;
+    let mut rng = factory.game_rng.sub_stream(OFFSET_SPAWNER, 0);
+    let ennemy_height = factory.ennemy_archetypes.largest_hitbox().y;
+    let mut grave_spots: Vec<Vec2> = graves.iter()
+        .filter(|(object, _)| matches!(object.get_type(), BackgroundObjectType::BigGrave | BackgroundObjectType::SmallGrave))
+        .map(|(_, collision_component)| {
+            let (x, y, _, h) = collision_component.get_hitbox();
+            Vec2::new(x, y - h / 2. - ennemy_height / 2. - SPAWNER_MARGIN)
+        })
+        .filter(|spot| factory.is_free(*spot))
+        .collect();
+    // Les requêtes n'ont pas d'ordre garanti, la graine doit toujours choisir les mêmes tombes
+    grave_spots.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+
+    let mut spots: Vec<Vec2> = grave_spots.choose_multiple(&mut rng, config.at_graves as usize).copied().collect();
+    let limit = game_config.map_size / 2. - SANCTUARY_WIDTH / 2.;
+    for _ in 0..config.generated {
+        match (0..SPAWNER_PLACEMENT_ATTEMPTS)
+            .map(|_| Vec2::new(rng.gen_range(-limit..limit), rng.gen_range(-limit..limit)))
+            .find(|spot| factory.is_free(*spot)) {
+            Some(spot) => spots.push(spot),
+            None => println!("No free spot left for a spawner after {} tries, skipped", SPAWNER_PLACEMENT_ATTEMPTS),
+        }
+    }
+
+    for (id, position) in spots.into_iter().enumerate() {
+        factory.commands.spawn(Spawner { id: id as u32, position, cooldown_left: config.cooldown });
+    }
+}
+
+// Waves come when their time is up, and in between each spawner tops its ennemies up to its cap,
+// one per cooldown. Only the spawners far enough from the player take part
+fn run_spawners(
+    mut director: ResMut<SpawnDirector>,
+    mut spawner_query: Query<&mut Spawner>,
+    ennemy_query: Query<&Ennemy>,
+    player_query: Query<&Player>,
+    mut factory: EnnemyFactory,
+    game_config: Res<GameConfig>,
+    fixed_time: Res<FixedTime>,
+) {
+    let config = &game_config.spawners;
+    let delta = fixed_time.period.as_secs_f32();
+    director.elapsed += delta;
+    let (player_x, player_y) = player_query.single().get_pos();
+    let is_far = |spawner: &Spawner| spawner.position.distance(Vec2::new(player_x, player_y)) >= config.min_player_distance;
+
+    let mut alive_total = 0;
+    let mut alive: HashMap<u32, u32> = HashMap::new();
+    for ennemy in ennemy_query.iter().filter(|ennemy| ennemy.health() > 0) {
+        alive_total += 1;
+        if let Some(spawner) = ennemy.spawner() {
+            *alive.entry(spawner).or_default() += 1;
+        }
+    }
+
+    let mut spawners: Vec<Mut<Spawner>> = spawner_query.iter_mut().collect();
+    spawners.sort_by_key(|spawner| spawner.id);
+
+    // Une vague attend qu'un spawner soit assez loin du joueur, et qu'il y ait de la place pour ses ennemis :
+    // ceux qui ne tiennent pas sortent dès que d'autres sont morts
+    let far_spawners: Vec<usize> = (0..spawners.len()).filter(|index| is_far(&spawners[*index])).collect();
+    if let Some(wave) = config.waves.get(director.next_wave).filter(|wave| wave.at <= director.elapsed) {
+        let count = wave.ennemies.saturating_sub(director.wave_spawned).min(config.max_ennemies.saturating_sub(alive_total));
+        if !far_spawners.is_empty() && count > 0 {
+            println!("Wave {}: {} of {} ennemies", director.next_wave + 1, director.wave_spawned + count, wave.ennemies);
+            for k in 0..count as usize {
+                let spawner = &spawners[far_spawners[(director.next_wave + director.wave_spawned as usize + k) % far_spawners.len()]];
+                let id = director.take_ennemy_id();
+                factory.spawn(id, spawner, wave.archetype.as_deref(), SPAWNER_SPREAD);
+                *alive.entry(spawner.id).or_default() += 1;
+            }
+            alive_total += count;
+            director.wave_spawned += count;
+        }
+        if director.wave_spawned >= wave.ennemies {
+            director.next_wave += 1;
+            director.wave_spawned = 0;
+        }
+    }
+
+    for spawner in spawners.iter_mut() {
+        // The cooldown starts over from the moment one of its ennemies is gone
+        if alive.get(&spawner.id).copied().unwrap_or(0) >= config.cap {
+            spawner.cooldown_left = config.cooldown;
+            continue;
+        }
+        spawner.cooldown_left -= delta;
+        if spawner.cooldown_left > 0. || !is_far(spawner) || alive_total >= config.max_ennemies {
+            continue;
+        }
+        let id = director.take_ennemy_id();
+        factory.spawn(id, spawner, None, 0.);
+        alive_total += 1;
+        spawner.cooldown_left = config.cooldown;
+    }
+}
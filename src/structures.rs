@@ -1,20 +1,30 @@
 use bevy::prelude::*;
 use rand::prelude::*;
-use crate::GameConfig;
-use crate::GameState;
+use crate::{GameState, SpawnSet, TickSet};
+use crate::atlases::AtlasRegistry;
 use crate::constants::*;
 use crate::collisions::*;
+use crate::controls::{Control, Controls};
+use crate::headless::*;
+use crate::player::Player;
+use crate::rng::GameRng;
+use crate::save::{SanctuarySave, TowerSave};
+use crate::config::GameConfig;
+use crate::spatial::SpatialIndex;
+use crate::triggers::{trigger_around, TriggerEvent, TriggerPhase};
 
 pub struct StructuresPlugin;
 
 impl Plugin for StructuresPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnExit(GameState::Menu), setup_structures)
-            .add_systems(OnExit(GameState::Loading), setup_structures)
+        app.add_systems(OnExit(GameState::Loading), setup_structures.in_set(SpawnSet::Structures))
+            .add_systems(FixedUpdate, (tower_interaction,
+                                                    sanctuary_interaction,
+                                                    update_collision_component,
+                                                ).chain().in_set(TickSet::Structures).run_if(in_state(GameState::Playing)))
             .add_systems(Update, (update_structures_pos,  
                                                     update_visibility, 
                                                     // change_visibility_with_keybinding, 
-                                                    update_collision_component,
                                                     update_sanctuary_color,
                                                 
                                             ).distributive_run_if(in_state(GameState::Playing)));
@@ -33,6 +43,15 @@ impl Tower {
         Tower { x, y}
     }
 
+    pub fn to_save(&self) -> TowerSave {
+        TowerSave { x: self.x, y: self.y }
+    }
+
+    pub fn restore(&mut self, save: &TowerSave) {
+        self.x = save.x;
+        self.y = save.y;
+    }
+
 }
 
 impl Collisionable for Tower {
@@ -43,6 +62,10 @@ impl Collisionable for Tower {
     fn get_hitbox(&self) -> (f32, f32, f32, f32) {
         (self.x, self.y, TOWER_WIDTH, TOWER_HEIGHT)
     }
+
+    fn collision_filter(&self) -> CollisionFilter {
+        CollisionFilter::STRUCTURE
+    }
 }  
 
 #[derive(Component, Clone)]
@@ -57,11 +80,9 @@ impl Sanctuary {
     pub fn new(x: f32, y: f32) -> Self {
         Sanctuary { x, y, visibility: true, unlocked: false }
     }
-    pub fn new_random_position(seed: u64) -> Self {
-        let mut rng = StdRng::seed_from_u64(seed);
-
-        let max_value_x = MAP_SIZE / 2. - SANCTUARY_WIDTH / 2.;
-        let max_value_y = MAP_SIZE / 2. - SANCTUARY_HEIGHT / 2.;
+    pub fn new_random_position(rng: &mut StdRng, map_size: f32) -> Self {
+        let max_value_x = map_size / 2. - SANCTUARY_WIDTH / 2.;
+        let max_value_y = map_size / 2. - SANCTUARY_HEIGHT / 2.;
 
         let x = rng.gen_range(-max_value_x..max_value_x);
         let y = rng.gen_range(-max_value_y..max_value_y);
@@ -85,6 +106,15 @@ impl Sanctuary {
     pub fn is_unlocked(&self) -> bool {
         self.unlocked
     }
+
+    pub fn to_save(&self) -> SanctuarySave {
+        SanctuarySave { x: self.x, y: self.y, visibility: self.visibility, unlocked: self.unlocked }
+    }
+
+    pub fn restore(&mut self, save: &SanctuarySave) {
+        self.visibility = save.visibility;
+        self.unlocked = save.unlocked;
+    }
 }
 
 impl Collisionable for Sanctuary {
@@ -93,90 +123,101 @@ impl Collisionable for Sanctuary {
     }
 
     fn get_hitbox(&self) -> (f32, f32, f32, f32) {
+        (self.x, self.y, SANCTUARY_WIDTH, SANCTUARY_HEIGHT)
+    }
+
+    // Nothing collides with a sanctuary until the tower reveals it
+    fn collision_filter(&self) -> CollisionFilter {
         if self.visibility {
-            (self.x, self.y, SANCTUARY_WIDTH, SANCTUARY_HEIGHT)
+            CollisionFilter::STRUCTURE
         } else {
-            (-1000., -1000., 0., 0.)
+            CollisionFilter::STRUCTURE.hidden()
         }
     }
 }
 
-fn does_collide_with_existing(sanctuary: &Sanctuary, query: &Query<&CollisionComponent>, added_sanctuaries: &[CollisionComponent]) -> bool {
-    query.iter().any(|existing| sanctuary.would_collide_with(existing))
-        || added_sanctuaries.iter().any(|added| sanctuary.would_collide_with(added))
+fn does_collide_with_existing(sanctuary: &Sanctuary, spatial_index: &SpatialIndex) -> bool {
+    let (x, y, w, h) = sanctuary.get_hitbox();
+    !spatial_index.colliders_near(x, y, w, h).is_empty()
 }
 
 fn setup_sanctuary(
     commands: &mut Commands, 
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    collision_query: &Query<&CollisionComponent>,
-    game_config: Res<GameConfig>,
+    atlases: &AtlasRegistry,
+    spatial_index: &mut SpatialIndex,
+    game_rng: Res<GameRng>,
+    game_config: &Res<GameConfig>,
 ) {
-    // Load the sanctuary texture
-    let texture_handle = asset_server.load("sanctuary.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(SANCTUARY_WIDTH, SANCTUARY_HEIGHT), 2, 1, Some(Vec2 { x: 1., y: 0. }), Some(Vec2::new(0., 0.))); // Assuming two textures side by side.
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
-
-    let mut added_sanctuaries = Vec::new();
-
-    for _ in 0..SANCTUARY_NB {
-        if let Some(mut sanctuary) = (0..10)
-            .map(|_| Sanctuary::new_random_position(game_config.seed + OFFSET_SANCTUARY + added_sanctuaries.len() as u64))
-            .find(|sanct| !does_collide_with_existing(sanct, &collision_query, &added_sanctuaries)) {
-
-            let collision_component = CollisionComponent::new_from_component(&sanctuary);
-            added_sanctuaries.push(collision_component.clone());
-
-            sanctuary.visibility = false;
-
-            commands.spawn(SpriteSheetBundle {
-                texture_atlas: texture_atlas_handle.clone(),
-                transform: Transform::from_xyz(sanctuary.x as f32, sanctuary.y as f32, Z_LAYER_STRUCTURES),
-                sprite: TextureAtlasSprite { index: 0, ..Default::default() }, // Use the first texture (red one)
-                ..Default::default()
-            })
-            .insert(collision_component)
-            .insert(sanctuary);
-            println!("Sanctuaire ajouté");
-        }
-
+    let texture_atlas_handle = atlases.atlas(SANCTUARY_SHEET);
+
+    for index in 0..game_config.sanctuary_nb {
+        // Every attempt draws a new spot from the stream of this sanctuary
+        let mut rng = game_rng.sub_stream(OFFSET_SANCTUARY, index as u64);
+        let Some(mut sanctuary) = (0..10)
+            .map(|_| Sanctuary::new_random_position(&mut rng, game_config.map_size))
+            .find(|sanct| !does_collide_with_existing(sanct, spatial_index)) else {
+            println!("No free spot left for sanctuary {}, skipped", index);
+            continue;
+        };
+
+        sanctuary.visibility = false;
+
+        let collision_component = CollisionComponent::new_from_component(&sanctuary);
+
+        let entity = spawn_sprite_sheet(commands, &texture_atlas_handle, 
+            TextureAtlasSprite { index: 0, ..Default::default() }, // Use the first texture (red one)
+            Transform::from_xyz(sanctuary.x, sanctuary.y, Z_LAYER_STRUCTURES))
+        .insert(collision_component.clone())
+        .with_children(|parent| {
+            parent.spawn(trigger_around(&sanctuary, INTERACTION_MARGIN));
+        })
+        .insert(sanctuary)
+        .id();
+        spatial_index.insert(entity, &collision_component);
+        println!("Sanctuaire ajouté");
     }
 }
 
 
 pub fn setup_structures(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    collision_query: Query<&CollisionComponent>,
-    texture_atlases: ResMut<Assets<TextureAtlas>>,
+    asset_server: Option<Res<AssetServer>>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    atlases: Res<AtlasRegistry>,
+    game_rng: Res<GameRng>,
     game_config: Res<GameConfig>,
 ) {
-    let tower_texture_handle = asset_server.load("tower.png");
 
-    let mut rng = StdRng::seed_from_u64(game_config.seed + OFFSET_TOWER);
-    let x = rng.gen_range(-MAP_SIZE / 2. + TOWER_WIDTH..MAP_SIZE / 2. - TOWER_WIDTH);
-    let y = rng.gen_range(-MAP_SIZE / 2. + TOWER_HEIGHT + PLAYER_HITBOX_HEIGHT..MAP_SIZE / 2. - TOWER_HEIGHT);
+    let map_size = game_config.map_size;
+    let mut rng = game_rng.sub_stream(OFFSET_TOWER, 0);
+    let x = rng.gen_range(-map_size / 2. + TOWER_WIDTH..map_size / 2. - TOWER_WIDTH);
+    let y = rng.gen_range(-map_size / 2. + TOWER_HEIGHT + PLAYER_HITBOX_HEIGHT..map_size / 2. - TOWER_HEIGHT);
 
     let tower = Tower::new(x, y);
-    setup_sanctuary(&mut commands, asset_server, texture_atlases, &collision_query, game_config);
+    setup_sanctuary(&mut commands, &atlases, &mut spatial_index, game_rng, &game_config);
 
     // Setup tower
     let collisioncomponent = CollisionComponent::new_from_component(&tower);
-    commands.spawn(SpriteBundle {
-        transform: Transform::from_xyz(0.0, 0.0, Z_LAYER_STRUCTURES),
-        sprite: Sprite {
-            custom_size: Some(Vec2::new(TOWER_WIDTH, TOWER_HEIGHT)),
+    let transform = Transform::from_xyz(0.0, 0.0, Z_LAYER_STRUCTURES);
+    let tower_entity = match asset_server {
+        Some(asset_server) => commands.spawn(SpriteBundle {
+            transform,
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(TOWER_WIDTH, TOWER_HEIGHT)),
+                ..Default::default()
+            },
+            texture: asset_server.load(TOWER_IMAGE),
             ..Default::default()
-        },
-        texture: tower_texture_handle,
-        ..Default::default()
+        }),
+        None => commands.spawn(TransformBundle::from_transform(transform)),
+    }
+    .insert(collisioncomponent.clone())
+    .with_children(|parent| {
+        parent.spawn(trigger_around(&tower, INTERACTION_MARGIN));
     })
     .insert(tower)
-    .insert(collisioncomponent);
-
-    
-
+    .id();
+    spatial_index.insert(tower_entity, &collisioncomponent);
 }
 
 fn update_structures_pos(mut query: Query<(&mut Transform, &Tower)>) {
@@ -202,16 +243,64 @@ fn update_visibility(mut query: Query<(&mut Visibility, &Sanctuary)>) {
 //     }
 // }
 
+// Triggers the player stands in this tick
+fn triggers_around_player(trigger_events: &mut EventReader<TriggerEvent>, player: Entity) -> Vec<Entity> {
+    trigger_events.iter()
+        .filter(|event| event.other == player && event.phase != TriggerPhase::Exit)
+        .map(|event| event.trigger)
+        .collect()
+}
+
+fn tower_interaction(
+    mut trigger_events: EventReader<TriggerEvent>,
+    player_query: Query<(Entity, &Player)>,
+    tower_query: Query<&Children, With<Tower>>,
+    controls: Res<Controls>,
+    query_sanctuary: Query<&mut Sanctuary>,
+    game_rng: ResMut<GameRng>,
+    nextstate: ResMut<NextState<GameState>>,
+) {
+    let (player_entity, player) = player_query.single();
+    let triggers = triggers_around_player(&mut trigger_events, player_entity);
+    let near_tower = tower_query.iter().flatten().any(|child| triggers.contains(child));
+    if near_tower && player.can_interact() && controls.just_pressed(Control::Interact) {
+        show_one_sanctuary(query_sanctuary, game_rng, nextstate);
+    }
+}
+
+fn sanctuary_interaction(
+    mut trigger_events: EventReader<TriggerEvent>,
+    mut player_query: Query<(Entity, &mut Player)>,
+    mut sanctuary_query: Query<(&mut Sanctuary, &Children)>,
+    controls: Res<Controls>,
+    game_config: Res<GameConfig>,
+) {
+    let (player_entity, mut player) = player_query.single_mut();
+    let triggers = triggers_around_player(&mut trigger_events, player_entity);
+    if !player.can_interact() || !controls.just_pressed(Control::Interact) {
+        return;
+    }
+    for (mut sanctuary, children) in sanctuary_query.iter_mut() {
+        if children.iter().any(|child| triggers.contains(child)) {
+            if sanctuary.unlock() {
+                player.heal(game_config.sanctuary_healing);
+            }
+            break;
+        }
+    }
+}
+
 fn update_collision_component(mut query: Query<(&mut CollisionComponent, &Sanctuary)>) {
     for (mut collision_component, sanctuary) in query.iter_mut() {
-        collision_component.update_hitbox(sanctuary);
+        // Only marked as changed when the sanctuary shows up, the navigation grid is rebuilt on changes
+        collision_component.set_if_neq(CollisionComponent::new_from_component(sanctuary));
     }
 }
 
-pub fn show_one_sanctuary(mut query: Query<&mut Sanctuary>, game_config: Res<GameConfig>, mut nextstate: ResMut<NextState<GameState>>) {
+pub fn show_one_sanctuary(mut query: Query<&mut Sanctuary>, mut game_rng: ResMut<GameRng>, mut nextstate: ResMut<NextState<GameState>>) {
     if are_all_visible_sanctuaries_unlocked(&query) {
 
-        let mut rng = StdRng::seed_from_u64(game_config.seed);
+        let rng = game_rng.stream(OFFSET_SANCTUARY_REVEAL);
         let mut sanctuaries: Vec<_> = query.iter_mut().filter(|sanctuary| !sanctuary.visibility).collect();
         let len = sanctuaries.len();
 
@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use crate::collisions::*;
+use crate::spatial::SpatialIndex;
+use crate::{GameState, TickSet};
+
+pub struct TriggerPlugin;
+
+impl Plugin for TriggerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TriggerEvent>()
+            .add_systems(FixedUpdate, (follow_owners,
+                                                    detect_triggers,
+                                                ).chain().in_set(TickSet::Triggers).run_if(in_state(GameState::Playing)));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TriggerPhase {
+    Enter,
+    Stay,
+    Exit,
+}
+
+// Sent every tick for each entity inside a trigger, Enter and Exit on the ticks it comes in and leaves
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TriggerEvent {
+    pub trigger: Entity,
+    pub other: Entity,
+    pub phase: TriggerPhase,
+}
+
+// Area that reports what enters it instead of blocking it. Its CollisionComponent gives its shape,
+// its filter the layers it reports. Spawned as a child, it follows its parent and hides with it
+#[derive(Component)]
+pub struct TriggerVolume {
+    filter: CollisionFilter,
+    inside: Vec<Entity>,
+}
+
+impl TriggerVolume {
+    pub fn new(filter: CollisionFilter) -> Self {
+        TriggerVolume { filter, inside: Vec::new() }
+    }
+
+    // Inside since the last detection
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.inside.contains(&entity)
+    }
+}
+
+// Trigger covering a collider and a margin around it
+pub fn trigger_around(owner: &dyn Collisionable, margin: f32) -> (TriggerVolume, CollisionComponent) {
+    let (x, y) = owner.get_pos();
+    let hitbox = Hitbox { shape: owner.collision_shape().grown(margin), offset: Vec2::ZERO };
+    (TriggerVolume::new(CollisionFilter::TRIGGER), CollisionComponent::from_hitbox(x, y, hitbox, CollisionFilter::TRIGGER))
+}
+
+fn follow_owners(
+    mut triggers: Query<(&TriggerVolume, &Parent, &mut CollisionComponent)>,
+    owners: Query<&CollisionComponent, Without<TriggerVolume>>,
+) {
+    for (volume, parent, mut collision_component) in triggers.iter_mut() {
+        let Ok(owner) = owners.get(parent.get()) else {
+            continue;
+        };
+        let filter = if owner.collision_filter().is_hidden() { volume.filter.hidden() } else { volume.filter };
+        // Only written when it changes, the spatial index reindexes every changed component
+        if collision_component.get_pos() != owner.get_pos() || collision_component.collision_filter() != filter {
+            let (x, y) = owner.get_pos();
+            collision_component.set_pos(x, y);
+            collision_component.set_filter(filter);
+        }
+    }
+}
+
+fn detect_triggers(
+    mut triggers: Query<(Entity, &mut TriggerVolume, &CollisionComponent)>,
+    colliders: Query<&CollisionComponent, Without<TriggerVolume>>,
+    spatial_index: Res<SpatialIndex>,
+    mut events: EventWriter<TriggerEvent>,
+) {
+    for (trigger, mut volume, collision_component) in triggers.iter_mut() {
+        let (x, y, w, h) = collision_component.get_hitbox();
+        // Sorted by entity, like every answer of the index
+        let inside: Vec<Entity> = spatial_index.colliders_near(x, y, w, h).into_iter()
+            .filter(|entity| colliders.get(*entity).is_ok_and(|other| {
+                collision_component.can_collide_with(other) && are_overlapping(collision_component.collider(), other.collider())
+            }))
+            .collect();
+
+        let event = |other: Entity, phase: TriggerPhase| TriggerEvent { trigger, other, phase };
+        events.send_batch(volume.inside.iter()
+            .filter(|entity| !inside.contains(entity))
+            .map(|entity| event(*entity, TriggerPhase::Exit)));
+        events.send_batch(inside.iter()
+            .map(|entity| event(*entity, if volume.inside.contains(entity) { TriggerPhase::Stay } else { TriggerPhase::Enter })));
+        volume.inside = inside;
+    }
+}